@@ -0,0 +1,141 @@
+use crate::config::Config;
+
+/// Replaces `$1`, `$2`, ... and `$@` in a macro's line by the arguments it was given
+fn substitute(name: &str, line: &str, args: &[&str]) -> Result<String, String> {
+    let mut substituted = String::new();
+    let mut chars = line.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if ch != '$' {
+            substituted.push(ch);
+            continue;
+        }
+
+        if chars.peek() == Some(&'@') {
+            chars.next();
+            substituted.push_str(&args.join(" "));
+            continue;
+        }
+
+        let mut number = String::new();
+        while let Some(digit) = chars.peek().filter(|ch| ch.is_ascii_digit()) {
+            number.push(*digit);
+            chars.next();
+        }
+
+        match number.parse::<usize>() {
+            Ok(0) => {
+                return Err(format!(
+                    "macro `{}` refers to `$0`. Arguments start at `$1`",
+                    name
+                ))
+            }
+            Ok(position) => match args.get(position - 1) {
+                Some(arg) => substituted.push_str(arg),
+                None => {
+                    return Err(format!(
+                        "macro `{}` expects at least {} argument(s), found {}",
+                        name,
+                        position,
+                        args.len()
+                    ))
+                }
+            },
+            // A lone `$` is kept as is
+            Err(_) => substituted.push('$'),
+        }
+    }
+
+    Ok(substituted)
+}
+
+/// Expands the aliases and macros at the start of the given line, and of the lines they expand to.
+/// A name is not expanded within its own expansion, so `alias show show hide-done` works as expected.
+pub fn expand(line: &str, config: &Config) -> Result<Vec<String>, String> {
+    expand_except(line, config, &mut vec![])
+}
+
+/// Expands the given line, leaving the names in `expanding` untouched
+fn expand_except<'a>(
+    line: &str,
+    config: &'a Config,
+    expanding: &mut Vec<&'a str>,
+) -> Result<Vec<String>, String> {
+    let words: Vec<&str> = line.split(' ').filter(|x| !x.is_empty()).collect();
+    let (name, args) = match words.split_first() {
+        Some((name, args)) if !expanding.contains(name) => (*name, args),
+        _ => return Ok(vec![line.into()]),
+    };
+
+    if let Some((name, expansion)) = config.aliases.get_key_value(name) {
+        let line = [&[expansion.as_str()], args].concat().join(" ");
+        expanding.push(name);
+        let expanded = expand_except(&line, config, expanding);
+        expanding.pop();
+        return expanded;
+    }
+
+    if let Some((name, lines)) = config.macros.get_key_value(name) {
+        let mut expanded = vec![];
+        expanding.push(name);
+        for line in lines {
+            let line = substitute(name, line, args)?;
+            expanded.extend(expand_except(&line, config, expanding)?);
+        }
+        expanding.pop();
+        return Ok(expanded);
+    }
+
+    Ok(vec![line.into()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_config() -> Config {
+        let mut config = Config::default();
+        config.set_alias("x".into(), "done".into()).unwrap();
+        config
+            .set_alias("show".into(), "show hide-done".into())
+            .unwrap();
+        config
+            .set_macro(
+                "ship".into(),
+                vec!["x $1".into(), "task 2 Announce $2".into(), "show".into()],
+            )
+            .unwrap();
+        config
+    }
+
+    #[test]
+    fn aliases() {
+        let config = sample_config();
+        assert_eq!(expand("x 1.2", &config), Ok(vec!["done 1.2".into()]));
+        assert_eq!(expand("show", &config), Ok(vec!["show hide-done".into()]));
+        assert_eq!(expand("done 1.2", &config), Ok(vec!["done 1.2".into()]));
+    }
+
+    #[test]
+    fn macros() {
+        let config = sample_config();
+        assert_eq!(
+            expand("ship 1.3 v1.0", &config),
+            Ok(vec![
+                "done 1.3".into(),
+                "task 2 Announce v1.0".into(),
+                "show hide-done".into()
+            ])
+        );
+        assert!(expand("ship 1.3", &config).is_err());
+    }
+
+    #[test]
+    fn substitution() {
+        assert_eq!(
+            substitute("m", "task $1 $@ costs $", &["2", "Buy", "milk"]),
+            Ok("task 2 2 Buy milk costs $".into())
+        );
+        assert!(substitute("m", "done $0", &["1.1"]).is_err());
+    }
+}
@@ -0,0 +1,89 @@
+use std::fmt;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::display;
+use crate::task::Task;
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+/// A finished task that was moved out of the todo list by `clean`
+pub struct ArchivedTask {
+    /// The name of the group the task belonged to
+    pub group_name: String,
+    /// The task itself
+    pub task: Task,
+    /// When the task was completed
+    pub completed_at: DateTime<Local>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Default, Clone)]
+#[serde(transparent)]
+/// Every task that was archived, oldest first
+pub struct Archive {
+    pub entries: Vec<ArchivedTask>,
+}
+
+impl Archive {
+    /// The archive as shown by `show archive`, with dates in the given strftime format
+    pub fn view<'a>(&'a self, date_format: &'a str) -> ArchiveView<'a> {
+        ArchiveView {
+            archive: self,
+            date_format,
+        }
+    }
+}
+
+/// The archive, ready to be shown with the user's date format
+pub struct ArchiveView<'a> {
+    archive: &'a Archive,
+    date_format: &'a str,
+}
+
+impl fmt::Display for ArchiveView<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.archive.entries.is_empty() {
+            return writeln!(f, "The archive is empty.");
+        }
+
+        for (i, entry) in self.archive.entries.iter().enumerate() {
+            writeln!(
+                f,
+                "{}. [x] - {} ({}, completed {} {})",
+                i + 1,
+                entry.task.description,
+                entry.group_name,
+                display::format_date(entry.completed_at.date_naive(), self.date_format),
+                entry.completed_at.format("%H:%M")
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn showing_the_archive() {
+        let mut archive = Archive::default();
+        assert_eq!(
+            archive.view("%d/%m/%Y").to_string(),
+            "The archive is empty.\n"
+        );
+
+        archive.entries.push(ArchivedTask {
+            group_name: "Chores".into(),
+            task: Task::new("Do the dishes".into()),
+            completed_at: Local.with_ymd_and_hms(2021, 3, 4, 18, 30, 0).unwrap(),
+        });
+        assert_eq!(
+            archive.view("%d/%m/%Y").to_string(),
+            "1. [x] - Do the dishes (Chores, completed 04/03/2021 18:30)\n"
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use std::io::BufRead;
+
+use colored::Colorize;
+
+use crate::command::Command;
+use crate::errors::Error;
+use crate::parser;
+use crate::todolist::TodoList;
+
+/// Runs the commands read from `input`, one per line, written as they would be in the REPL.
+/// Blank lines and lines starting with `#` are skipped.
+/// Unless `keep_going` is set, the first line that fails stops the run.
+/// Returns how many lines failed.
+pub fn run(list: &mut TodoList, input: impl BufRead, keep_going: bool) -> Result<usize, Error> {
+    let mut failed = 0;
+
+    for (line_idx, line) in input.lines().enumerate() {
+        let line = line?;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        let errors_before = list.errors_reported;
+        let commands = parser::parse(trimmed, &list.config);
+        let mut succeeded = !commands.is_empty();
+        for command in commands {
+            if command == Command::NoOp {
+                succeeded = false;
+                continue;
+            }
+            list.evaluate(command);
+        }
+        succeeded &= list.errors_reported == errors_before;
+
+        if !succeeded {
+            failed += 1;
+            println!(
+                "{}: line {} failed: `{}`",
+                "error".red(),
+                line_idx + 1,
+                trimmed
+            );
+            if !keep_going {
+                break;
+            }
+        }
+    }
+
+    Ok(failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::*;
+    use crate::task::TaskGroup;
+
+    fn sample_list() -> Result<TodoList, Error> {
+        TodoList::try_from(vec![TaskGroup {
+            name: "Chores".into(),
+            tasks: vec![],
+        }])
+    }
+
+    #[test]
+    fn script() -> Result<(), Error> {
+        let mut list = sample_list()?;
+        let script =
+            "# Weekly chores\n\ntask 1 Do the dishes\n  task 1 Clean the house\ndone 1.2\n";
+
+        assert_eq!(run(&mut list, script.as_bytes(), false)?, 0);
+        let tasks = &list.task_groups[0].tasks;
+        assert_eq!(tasks.len(), 2);
+        assert!(tasks[1].is_done);
+
+        Ok(())
+    }
+
+    #[test]
+    fn stopping_on_errors() -> Result<(), Error> {
+        let script = "task 1 Do the dishes\ndone 3.1\nfrobnicate\ntask 1 Clean the house\n";
+
+        let mut list = sample_list()?;
+        assert_eq!(run(&mut list, script.as_bytes(), false)?, 1);
+        assert_eq!(list.task_groups[0].tasks.len(), 1);
+
+        let mut list = sample_list()?;
+        assert_eq!(run(&mut list, script.as_bytes(), true)?, 2);
+        assert_eq!(list.task_groups[0].tasks.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn failing_on_bad_task_numbers() -> Result<(), Error> {
+        let script = "task 1 Do the dishes\nremove task 1.99\ndone 1.1\n";
+
+        let mut list = sample_list()?;
+        assert_eq!(run(&mut list, script.as_bytes(), false)?, 1);
+        assert!(!list.task_groups[0].tasks[0].is_done);
+
+        Ok(())
+    }
+
+    #[test]
+    fn failing_on_group_zero() -> Result<(), Error> {
+        let script = "task 0 Do the dishes\nremove group 0\ntask 1 Do the laundry\n";
+
+        let mut list = sample_list()?;
+        let groups = list.task_groups.len();
+        assert_eq!(run(&mut list, script.as_bytes(), true)?, 2);
+        assert_eq!(list.task_groups.len(), groups);
+        assert!(list.task_groups[0]
+            .tasks
+            .iter()
+            .any(|task| task.description == "Do the laundry"));
+
+        Ok(())
+    }
+}
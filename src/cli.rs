@@ -1,11 +1,43 @@
-use std::convert::TryFrom;
+use std::{convert::TryFrom, path::PathBuf};
 
-use clap::{self, Arg};
+use clap::{self, Arg, SubCommand};
 
 use crate::command::Command;
-use crate::errors;
+use crate::display::DisplayOptions;
+use crate::errors::{self, Error};
+use crate::filter;
+use crate::formats::{self, ConversionOptions};
 use crate::parser;
 
+/// The arguments shared by `import` and `export`
+fn conversion_args() -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("format")
+            .long("format")
+            .takes_value(true)
+            .possible_values(formats::FORMAT_NAMES)
+            .help("The format to use. Guessed from the file's extension if omitted"),
+        Arg::with_name("group-as")
+            .long("group-as")
+            .takes_value(true)
+            .possible_values(&["project", "context"])
+            .help("Whether groups are todo.txt projects or contexts. Defaults to projects"),
+        Arg::with_name("column")
+            .long("column")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .value_name("FIELD=HEADER")
+            .validator(|mapping| ConversionOptions::default().map_column(&mapping))
+            .help("Uses the CSV column named HEADER for FIELD. Ex.: `--column description=Task`"),
+        Arg::with_name("delimiter")
+            .long("delimiter")
+            .takes_value(true)
+            .validator(|delimiter| ConversionOptions::default().set_delimiter(&delimiter))
+            .help("The character separating CSV fields, or `tab`. Defaults to a comma"),
+    ]
+}
+
 pub fn get_matches() -> clap::ArgMatches<'static> {
     clap::App::new("dodo")
         .version("0.1.0")
@@ -42,11 +74,220 @@ pub fn get_matches() -> clap::ArgMatches<'static> {
             .value_name("G")
             .help("Marks a task as done. Ex.: `dodo -d 3.2` marks the third task of the second group as done."),
         )
+        .arg(
+            Arg::with_name("dates")
+            .required(false)
+            .long("dates")
+            .takes_value(false)
+            .help("Shows when each task was created, completed and last modified"),
+        )
+        .arg(
+            Arg::with_name("find")
+            .required(false)
+            .long("find")
+            .short("f")
+            .takes_value(true)
+            .multiple(true)
+            .allow_hyphen_values(true)
+            .value_name("TEXT")
+            .help("Searches every group for tasks matching TEXT. Prefix it with `-i` to ignore case, `-r` for a regular expression or `-f` for a fuzzy search."),
+        )
+        .arg(
+            Arg::with_name("filter")
+            .required(false)
+            .long("filter")
+            .short("q")
+            .takes_value(true)
+            .multiple(true)
+            .value_name("QUERY")
+            .help("Only shows the tasks matching QUERY. Ex.: `dodo -q pending due<7d +backend`."),
+        )
+        .arg(
+            Arg::with_name("sort")
+            .required(false)
+            .long("sort")
+            .short("s")
+            .takes_value(true)
+            .value_name("KEY")
+            .possible_values(&["created", "due", "priority", "alpha", "done"])
+            .help("Shows the tasks sorted by KEY, without changing their stored order"),
+        )
+        .arg(
+            Arg::with_name("reverse")
+            .required(false)
+            .long("reverse")
+            .takes_value(false)
+            .help("Reverses the order given by --sort"),
+        )
+        .arg(
+            Arg::with_name("flat")
+            .required(false)
+            .long("flat")
+            .takes_value(false)
+            .help("Shows every task in a single list instead of under their groups"),
+        )
+        .arg(
+            Arg::with_name("hide-done")
+            .required(false)
+            .long("hide-done")
+            .takes_value(false)
+            .help("Hides the tasks that are done"),
+        )
+        .arg(
+            Arg::with_name("counts")
+            .required(false)
+            .long("counts")
+            .takes_value(false)
+            .help("Only shows how many tasks are pending in each group"),
+        )
+        .arg(
+            Arg::with_name("collapse")
+            .required(false)
+            .long("collapse")
+            .takes_value(false)
+            .help("Shows groups whose tasks are all done as a single line"),
+        )
+        .arg(
+            Arg::with_name("limit")
+            .required(false)
+            .long("limit")
+            .takes_value(true)
+            .value_name("N")
+            .help("Shows at most N tasks per group"),
+        )
+        .arg(
+            Arg::with_name("stats")
+            .required(false)
+            .long("stats")
+            .takes_value(false)
+            .help("Shows statistics such as completion rates and the oldest open tasks"),
+        )
+        .arg(
+            Arg::with_name("clean")
+            .required(false)
+            .long("clean")
+            .short("c")
+            .takes_value(true)
+            .min_values(0)
+            .max_values(1)
+            .value_name("G")
+            .help("Archives the done tasks of group G, or of every group if G is omitted."),
+        )
+        .arg(
+            Arg::with_name("stdin")
+            .required(false)
+            .possible_value("-")
+            .value_name("-")
+            .help("Runs the commands read from stdin, one per line, as they would be typed in the REPL"),
+        )
+        .arg(
+            Arg::with_name("keep-going")
+            .required(false)
+            .long("keep-going")
+            .takes_value(false)
+            .global(true)
+            .help("Keeps running the following commands when one of them fails, instead of stopping without saving"),
+        )
+        .arg(
+            Arg::with_name("dry-run")
+            .required(false)
+            .long("dry-run")
+            .takes_value(false)
+            .global(true)
+            .conflicts_with("edit")
+            .help("Shows the changes the commands would make to the list, without saving them"),
+        )
+        .subcommand(
+            SubCommand::with_name("run")
+            .about("Runs the commands in FILE, one per line, as they would be typed in the REPL. Lines starting with `#` are ignored")
+            .arg(Arg::with_name("file").required(true).value_name("FILE"))
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+            .about("Writes the list in another format")
+            .args(&conversion_args())
+            .arg(
+                Arg::with_name("output")
+                .long("output")
+                .short("o")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Writes to FILE instead of the standard output")
+            )
+        )
+        .subcommand(
+            SubCommand::with_name("import")
+            .about("Reads the tasks of FILE, written in another format, into the list")
+            .arg(Arg::with_name("file").required(true).value_name("FILE"))
+            .args(&conversion_args())
+            .arg(
+                Arg::with_name("replace")
+                .long("replace")
+                .takes_value(false)
+                .help("Replaces the whole list with the imported tasks instead of merging them into it")
+            )
+        )
+        .subcommand(
+            SubCommand::with_name("sync")
+            .about("Commits the list to git, then pulls and pushes the changes of the remote set with `dodo config set sync.remote URL`. Changes made in both places are merged task by task")
+        )
+        .subcommand(
+            SubCommand::with_name("watch")
+            .about("Shows the list and shows it again whenever it changes, e.g. from another terminal. Display options go before `watch`. Ex.: `dodo --hide-done watch`")
+            .arg(
+                Arg::with_name("poll")
+                .long("poll")
+                .takes_value(false)
+                .help("Checks the files for changes every second instead of relying on filesystem notifications")
+            )
+        )
+        .subcommand(
+            SubCommand::with_name("config")
+            .about("Reads or changes the configuration, stored in config.ron")
+            .subcommand(
+                SubCommand::with_name("list")
+                .about("Shows every key along with its value")
+            )
+            .subcommand(
+                SubCommand::with_name("get")
+                .about("Shows the value of KEY")
+                .arg(Arg::with_name("key").required(true).value_name("KEY"))
+            )
+            .subcommand(
+                SubCommand::with_name("set")
+                .about("Sets KEY to VALUE. Ex.: `dodo config set date_format %d/%m/%Y`")
+                .arg(Arg::with_name("key").required(true).value_name("KEY"))
+                .arg(Arg::with_name("value").required(true).multiple(true).allow_hyphen_values(true).value_name("VALUE"))
+            )
+        )
         .get_matches()
 }
 
+/// Where to read the commands of batch mode from
+pub enum Script {
+    Stdin,
+    File(PathBuf),
+}
+
 pub struct CommandLineArguments {
     pub should_start_repl: bool,
+    /// False when the arguments only deal with something else than the list, such as `config`
+    pub should_show_list: bool,
+    /// Whether the arguments only read or change the configuration, which doesn't need the list
+    pub only_config: bool,
+    /// The commands to be run in batch mode, if any
+    pub script: Option<Script>,
+    /// Whether batch mode goes on after a command fails
+    pub keep_going: bool,
+    /// Whether the changes are shown instead of being saved
+    pub dry_run: bool,
+    /// Whether to sync the list with its git remote
+    pub sync: bool,
+    /// Whether to keep showing the list as it changes
+    pub watch: bool,
+    /// Whether changes are watched by checking the files regularly instead of with notifications
+    pub poll: bool,
+    pub display_options: DisplayOptions,
     pub commands: Vec<Command>
 }
 
@@ -59,25 +300,132 @@ impl TryFrom<clap::ArgMatches<'static>> for CommandLineArguments {
         let parse_options = |matches: &clap::ArgMatches<'static>, commands: & mut Vec<Command>, word | {
             if matches.is_present(word) {
                 let mut values: Vec<&str> = vec![word];
-                let mut args: Vec<&str> = matches.values_of(word).unwrap().collect();
-                values.append(&mut args);
+                if let Some(args) = matches.values_of(word) {
+                    values.extend(args);
+                }
                 // TODO: stop execution when a NoOp is found?
-                commands.push(parser::parse(&values.join(" ")));
+                commands.push(parser::parse_command(&values.join(" ")));
             }
         };
 
         let should_start_repl = matches.is_present("edit");
+        let filter = match matches.values_of("filter") {
+            Some(words) => {
+                let words: Vec<&str> = words.flat_map(|word| word.split(' ')).filter(|x| !x.is_empty()).collect();
+                Some(filter::parse(&words).map_err(Error::InvalidQuery)?)
+            }
+            None => None,
+        };
 
-        let options = ["task", "group", "done"];
+        let sort = match matches.value_of("sort") {
+            Some(key) => Some(key.parse().map_err(Error::InvalidQuery)?),
+            None => None,
+        };
+
+        let display_options = DisplayOptions {
+            show_timestamps: matches.is_present("dates"),
+            filter,
+            sort,
+            reverse: matches.is_present("reverse"),
+            flat: matches.is_present("flat"),
+            hide_done: Some(true).filter(|_| matches.is_present("hide-done")),
+            counts_only: Some(true).filter(|_| matches.is_present("counts")),
+            collapse_done: Some(true).filter(|_| matches.is_present("collapse")),
+            limit: match matches.value_of("limit") {
+                Some(limit) => Some(limit.parse().map_err(|_| Error::ParseIntError)?),
+                None => None,
+            },
+            date_format: None,
+        };
+
+        let options = ["task", "group", "done", "clean", "find", "stats"];
 
         for option in &options {
             parse_options(&matches, &mut commands, option);
         }
 
+        // The values were already checked by the arguments' validators
+        let conversion_options = |args: &clap::ArgMatches<'static>| {
+            let mut options = ConversionOptions {
+                format: args.value_of("format").and_then(|format| format.parse().ok()),
+                ..Default::default()
+            };
+            let _ = options.set_group_marker(args.value_of("group-as").unwrap_or("project"));
+            for mapping in args.values_of("column").into_iter().flatten() {
+                let _ = options.map_column(mapping);
+            }
+            if let Some(delimiter) = args.value_of("delimiter") {
+                let _ = options.set_delimiter(delimiter);
+            }
+            options
+        };
+
+        let mut should_show_list = true;
+        // Global flags given after a subcommand only show up in the subcommand's matches
+        let is_present_anywhere = |flag: &str| {
+            matches.is_present(flag) || matches.subcommand().1.is_some_and(|sub| sub.is_present(flag))
+        };
+        let keep_going = is_present_anywhere("keep-going");
+        let dry_run = is_present_anywhere("dry-run");
+
+        let mut script = None;
+        if matches.is_present("stdin") {
+            script = Some(Script::Stdin);
+        }
+        if let Some(run) = matches.subcommand_matches("run") {
+            script = run.value_of("file").map(|file| Script::File(file.into()));
+        }
+
+        if let Some(export) = matches.subcommand_matches("export") {
+            should_show_list = false;
+            commands.push(Command::Export(
+                conversion_options(export),
+                export.value_of("output").map(PathBuf::from),
+            ));
+        }
+        if let Some(import) = matches.subcommand_matches("import") {
+            commands.push(Command::Import(
+                conversion_options(import),
+                import.value_of("file").unwrap_or_default().into(),
+                import.is_present("replace"),
+            ));
+        }
+        let sync = matches.subcommand_matches("sync").is_some();
+        let watch = matches.subcommand_matches("watch");
+        if sync || watch.is_some() {
+            should_show_list = false;
+        }
+
+        if let Some(config) = matches.subcommand_matches("config") {
+            should_show_list = false;
+            commands.push(match config.subcommand() {
+                ("get", Some(args)) => Command::ConfigGet(args.value_of("key").unwrap_or_default().into()),
+                ("set", Some(args)) => Command::ConfigSet(
+                    args.value_of("key").unwrap_or_default().into(),
+                    args.values_of("value").map(|words| words.collect::<Vec<_>>().join(" ")).unwrap_or_default(),
+                ),
+                _ => Command::ConfigList,
+            });
+        }
+        let only_config = matches.subcommand_matches("config").is_some()
+            && commands.len() == 1
+            && script.is_none()
+            && !should_start_repl
+            && !dry_run;
+
         Ok(
             Self {
                 commands,
-                should_start_repl
+                should_start_repl,
+                should_show_list,
+                only_config,
+                script,
+                keep_going,
+                dry_run,
+                sync,
+                watch: watch.is_some(),
+                poll: watch.is_some_and(|watch| watch.is_present("poll")),
+                display_options
             }
         )
     }
@@ -1,8 +1,41 @@
+use std::path::PathBuf;
+
+use crate::display::DisplayOptions;
+use crate::formats::ConversionOptions;
+use crate::search::SearchQuery;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+/// A reference to one or more tasks, as given by the user
+pub enum TaskRef {
+    /// A single task, e.g. `2.3`.
+    /// Arguments are (number of the group, number of the task)
+    Single(u16, u16),
+    /// An inclusive range of tasks within a group, e.g. `2.1-5`.
+    /// Arguments are (number of the group, first task, last task)
+    Range(u16, u16, u16),
+    /// Every task of a group, e.g. `1.*`.
+    /// Argument is the number of the group
+    WholeGroup(u16),
+}
+
+impl std::fmt::Display for TaskRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TaskRef::Single(group_no, task_no) => write!(f, "{}.{}", group_no, task_no),
+            TaskRef::Range(group_no, first, last) => write!(f, "{}.{}-{}", group_no, first, last),
+            TaskRef::WholeGroup(group_no) => write!(f, "{}.*", group_no),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum Command {
     /// Flip a task from not done to done or vice-versa
     /// Arguments are (number of the group, number of the task)
     FlipTask(u16, u16),
+    /// Flips several tasks at once. Either all of them are flipped or none is.
+    /// Argument is the list of task references
+    FlipTasks(Vec<TaskRef>),
     /// Adds a new task to a group;
     /// Arguments are (task description, group number)
     AddTask(String, u16),
@@ -12,11 +45,136 @@ pub enum Command {
     /// Removes a task from a group.
     /// Arguments are (number of the group, number of the task)
     RemoveTask(u16, u16),
+    /// Removes several tasks at once. Either all of them are removed or none is.
+    /// Argument is the list of task references
+    RemoveTasks(Vec<TaskRef>),
     /// Removes a group
     /// Argument is the number of the group to be removed
     RemoveGroup(u16),
     /// Shows the entire todo list
-    ShowList,
+    /// Argument is the options on how to display it
+    ShowList(DisplayOptions),
+    /// Moves every done task into the archive.
+    /// Argument is the number of the group to be cleaned, or None for every group
+    Clean(Option<u16>),
+    /// Shows the archived tasks
+    ShowArchive,
+    /// Shows statistics about the list, such as completion rates
+    ShowStats,
+    /// Searches for tasks across every group
+    /// Argument is the search to be made
+    Find(SearchQuery),
+    /// Persists the given display options as the ones used by default
+    /// Argument is the options to be persisted. Options that are None are left as they were
+    SetDisplaySettings(DisplayOptions),
+    /// Shows the display options used by default
+    ShowDisplaySettings,
+    /// Saves a query to be used by `show` as `@name`
+    /// Arguments are (name of the query, the query itself)
+    SaveQuery(String, String),
+    /// Removes a saved query
+    /// Argument is the name of the query
+    RemoveQuery(String),
+    /// Lists every saved query
+    ListQueries,
+    /// Shows the value of a configuration key
+    /// Argument is the key, e.g. `display.limit`
+    ConfigGet(String),
+    /// Sets a configuration key
+    /// Arguments are (the key, its new value)
+    ConfigSet(String, String),
+    /// Shows every configuration key along with its value
+    ConfigList,
+    /// Defines an alias, replacing the first word of a line by some text
+    /// Arguments are (the alias, the text it expands to)
+    SetAlias(String, String),
+    /// Defines a macro, replacing a line by a sequence of lines
+    /// Arguments are (the name of the macro, the lines it expands to)
+    SetMacro(String, Vec<String>),
+    /// Removes an alias or a macro
+    /// Argument is its name
+    RemoveAlias(String),
+    /// Lists every alias and macro
+    ListAliases,
+    /// Writes the list in another format
+    /// Arguments are (options on how to convert the list, the file to write to, or None for stdout)
+    Export(ConversionOptions, Option<PathBuf>),
+    /// Reads tasks written in another format into the list
+    /// Arguments are (options on how to convert the tasks, the file to read,
+    /// whether the tasks replace the whole list instead of being merged into it)
+    Import(ConversionOptions, PathBuf, bool),
+    /// Moves a task from the archive back into its group
+    /// Argument is the number of the archive entry
+    Restore(u16),
+    /// Saves the list and the configuration
+    Save,
+    /// Loads the list and the configuration again, discarding the changes that weren't saved.
+    /// Argument is whether to discard them without a warning, as given by `reload!`
+    Reload(bool),
+    /// Ends the REPL session.
+    /// Argument is whether to quit without a warning when there are unsaved changes, as given by `quit!`
+    Quit(bool),
     /// Indicates that no operation should be used
     NoOp,
 }
+
+/// Joins task references for a summary, e.g. `1.2, 3.*`
+fn join_refs(refs: &[TaskRef]) -> String {
+    let refs: Vec<String> = refs.iter().map(TaskRef::to_string).collect();
+    refs.join(", ")
+}
+
+impl Command {
+    /// Whether the command only shows things, leaving the list and the configuration as they were.
+    /// Exports aren't, since they may give tasks the UIDs they are exported with
+    pub fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            Command::ShowList(_)
+                | Command::ShowArchive
+                | Command::ShowStats
+                | Command::Find(_)
+                | Command::ShowDisplaySettings
+                | Command::ListQueries
+                | Command::ConfigGet(_)
+                | Command::ConfigList
+                | Command::ListAliases
+                | Command::Save
+                | Command::Reload(_)
+                | Command::Quit(_)
+                | Command::NoOp
+        )
+    }
+
+    /// Describes what the command changes, e.g. `Add group \`Work\``. Used as commit messages
+    /// when the list is synced
+    pub fn summary(&self) -> String {
+        match self {
+            Command::FlipTask(group_no, task_no) => format!("Flip task {}.{}", group_no, task_no),
+            Command::FlipTasks(refs) => format!("Flip tasks {}", join_refs(refs)),
+            Command::AddTask(description, group_no) => {
+                format!("Add `{}` to group {}", description, group_no)
+            }
+            Command::AddGroup(name) => format!("Add group `{}`", name),
+            Command::RemoveTask(group_no, task_no) => {
+                format!("Remove task {}.{}", group_no, task_no)
+            }
+            Command::RemoveTasks(refs) => format!("Remove tasks {}", join_refs(refs)),
+            Command::RemoveGroup(group_no) => format!("Remove group {}", group_no),
+            Command::Clean(Some(group_no)) => format!("Clean group {}", group_no),
+            Command::Clean(None) => "Clean every group".into(),
+            Command::Restore(entry_no) => format!("Restore archived task {}", entry_no),
+            Command::SetDisplaySettings(_) => "Change the display settings".into(),
+            Command::SaveQuery(name, _) => format!("Save query @{}", name),
+            Command::RemoveQuery(name) => format!("Remove query @{}", name),
+            Command::ConfigSet(key, value) => format!("Set `{}` to `{}`", key, value),
+            Command::SetAlias(name, _) => format!("Define alias `{}`", name),
+            Command::SetMacro(name, _) => format!("Define macro `{}`", name),
+            Command::RemoveAlias(name) => format!("Remove alias `{}`", name),
+            Command::Export(..) => "Export the list".into(),
+            Command::Import(_, path, _) => format!("Import {}", path.display()),
+            // The other commands don't change the list
+            _ => "Update the list".into(),
+        }
+    }
+}
@@ -0,0 +1,464 @@
+use std::{collections::BTreeMap, fmt, fs, path::Path};
+
+use chrono::Local;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+
+use crate::display::{self, DisplaySettings, DEFAULT_DATE_FORMAT};
+use crate::errors::Error;
+use crate::storage::{to_ron, Backend};
+use crate::sync::SyncSettings;
+
+static CONFIG_FILE_NAME: &str = "config.ron";
+/// Where the display settings were stored before there was a config file
+static SETTINGS_FILE_NAME: &str = "settings.ron";
+
+/// Every key that can be read or written with `config get` and `config set`
+pub const KEYS: &[&str] = &[
+    "prompt",
+    "date_format",
+    "colors",
+    "display.hide_done",
+    "display.counts_only",
+    "display.collapse_done",
+    "display.limit",
+    "storage",
+    "pretty_ron",
+    "autosave",
+    "sync.remote",
+    "sync.auto_commit",
+];
+
+/// Keys of the config file holding tables, which are edited with `alias` and `macro`
+const TABLES: &[&str] = &["aliases", "macros"];
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(default)]
+/// The user's preferences, stored in `config.ron`
+pub struct Config {
+    /// The prompt shown by the REPL
+    pub prompt: String,
+    /// How dates are shown, in strftime format
+    pub date_format: String,
+    /// Whether the output should be coloured
+    pub colors: bool,
+    /// The display options used by default
+    pub display: DisplaySettings,
+    /// Words replaced by the given text at the start of a REPL line, e.g. `x` for `done`
+    pub aliases: BTreeMap<String, String>,
+    /// Words replaced by the given sequence of REPL lines. `$1`, `$2`, ... and `$@` in the lines
+    /// are replaced by the arguments the macro was given
+    pub macros: BTreeMap<String, Vec<String>>,
+    /// Where the list is stored. Takes effect the next time dodo starts
+    pub storage: Backend,
+    /// Whether the RON files are pretty-printed, with a task per line, rather than written on
+    /// a single line
+    pub pretty_ron: bool,
+    /// Whether the REPL saves the list after every command that changes it, instead of when it exits
+    pub autosave: bool,
+    /// How the list is synced with git by `dodo sync`
+    pub sync: SyncSettings,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            prompt: ">> ".into(),
+            date_format: DEFAULT_DATE_FORMAT.into(),
+            colors: true,
+            display: DisplaySettings::default(),
+            aliases: BTreeMap::new(),
+            macros: BTreeMap::new(),
+            storage: Backend::default(),
+            pretty_ron: true,
+            autosave: true,
+            sync: SyncSettings::default(),
+        }
+    }
+}
+
+/// Builds an error message for an unknown key, suggesting the closest of the `known` ones
+fn unknown_key(key: &str, known: &[&str]) -> String {
+    let closest = known
+        .iter()
+        .map(|candidate| (strsim::levenshtein(key, candidate), candidate))
+        .min()
+        .filter(|(distance, _)| *distance <= 3);
+
+    match closest {
+        Some((_, closest)) => format!("unknown key `{}`. Did you mean `{}`?", key, closest),
+        None => format!(
+            "unknown key `{}`. Valid keys are: {}",
+            key,
+            known.join(", ")
+        ),
+    }
+}
+
+/// Checks every key of the given RON map (and of its nested maps) against the known keys
+fn check_keys(value: &ron::Value, prefix: &str) -> Result<(), String> {
+    let map = match value {
+        ron::Value::Map(map) => map,
+        _ => return Ok(()),
+    };
+
+    for (key, value) in map.iter() {
+        let key = match key {
+            ron::Value::String(key) => format!("{}{}", prefix, key),
+            other => return Err(format!("expected a key, found `{:?}`", other)),
+        };
+
+        let is_section = KEYS
+            .iter()
+            .any(|known| known.starts_with(&format!("{}.", key)));
+        if is_section {
+            check_keys(value, &format!("{}.", key))?;
+        } else if !KEYS.contains(&key.as_str()) && !TABLES.contains(&key.as_str()) {
+            let known: Vec<&str> = KEYS.iter().chain(TABLES).copied().collect();
+            return Err(unknown_key(&key, &known));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that the given alias or macro name is a single word
+fn check_name(name: &str) -> Result<(), String> {
+    match name.is_empty() || name.contains(char::is_whitespace) {
+        true => Err(format!("`{}` is not a valid alias or macro name", name)),
+        false => Ok(()),
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "on" | "yes" => Ok(true),
+        "false" | "off" | "no" => Ok(false),
+        value => Err(format!("expected `true` or `false`, found `{}`", value)),
+    }
+}
+
+impl Config {
+    /// Loads the configuration from the given folder, using the defaults if there's no config file
+    pub fn load(config_path: &Path) -> Result<Self, Error> {
+        let config_file_path = config_path.join(CONFIG_FILE_NAME);
+        if !config_file_path.exists() {
+            return Self::migrate(config_path);
+        }
+
+        Self::from_ron(&fs::read_to_string(config_file_path)?).map_err(Error::InvalidConfig)
+    }
+
+    /// Loads the configuration like `load`, but without rejecting unknown keys or invalid values,
+    /// so that `config set` can fix them. Unknown keys are dropped
+    pub fn load_unchecked(config_path: &Path) -> Result<Self, Error> {
+        match Self::load(config_path) {
+            Err(Error::InvalidConfig(reason)) => {
+                println!(
+                    "{}: {}. Fix it with `dodo config set`.",
+                    "warning".yellow(),
+                    reason
+                );
+                let contents = fs::read_to_string(config_path.join(CONFIG_FILE_NAME))?;
+                ron::de::from_str(&contents).map_err(|err| Error::InvalidConfig(err.to_string()))
+            }
+            result => result,
+        }
+    }
+
+    /// Creates the config file of a folder that doesn't have one yet,
+    /// keeping the display settings found in `settings.ron`
+    fn migrate(config_path: &Path) -> Result<Self, Error> {
+        let mut config = Self::default();
+        let settings_file_path = config_path.join(SETTINGS_FILE_NAME);
+        if !settings_file_path.exists() {
+            return Ok(config);
+        }
+
+        config.display = ron::de::from_str(&fs::read_to_string(settings_file_path)?)
+            .map_err(|err| Error::InvalidConfig(format!("{}: {}", SETTINGS_FILE_NAME, err)))?;
+        config.save(config_path)?;
+        println!(
+            "{}: moved the display settings of `{}` to `{}`. `{}` is kept as a backup.",
+            "info".yellow(),
+            SETTINGS_FILE_NAME,
+            CONFIG_FILE_NAME,
+            SETTINGS_FILE_NAME
+        );
+        Ok(config)
+    }
+
+    /// Parses and validates a configuration in the RON format
+    pub fn from_ron(contents: &str) -> Result<Self, String> {
+        let value: ron::Value = ron::de::from_str(contents).map_err(|err| err.to_string())?;
+        check_keys(&value, "")?;
+
+        let config: Config = value.into_rust().map_err(|err| err.to_string())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Applies the settings that take effect globally, such as colours
+    pub fn apply(&self) {
+        match self.colors {
+            true => colored::control::unset_override(),
+            false => colored::control::set_override(false),
+        }
+    }
+
+    pub fn save(&self, config_path: &Path) -> Result<(), Error> {
+        let serialized_data = to_ron(self, self.pretty_ron, usize::MAX)?;
+        fs::write(config_path.join(CONFIG_FILE_NAME), serialized_data)?;
+        Ok(())
+    }
+
+    /// Checks that every value makes sense
+    pub fn validate(&self) -> Result<(), String> {
+        if self.prompt.is_empty() {
+            return Err("`prompt` cannot be empty".into());
+        }
+
+        // Formats that need more than a date, such as `%H:%M`, are rejected along with invalid ones
+        if display::try_format_date(Local::now().date_naive(), &self.date_format).is_err() {
+            return Err(format!(
+                "`{}` is not a valid `date_format`. Expected a strftime date format such as `%Y-%m-%d`",
+                self.date_format
+            ));
+        }
+
+        if self.display.limit == Some(0) {
+            return Err("`display.limit` must be greater than zero, or `none`".into());
+        }
+
+        for (name, expansion) in &self.aliases {
+            check_name(name)?;
+            if expansion.trim().is_empty() {
+                return Err(format!("alias `{}` expands to nothing", name));
+            }
+        }
+
+        for (name, lines) in &self.macros {
+            check_name(name)?;
+            if self.aliases.contains_key(name) {
+                return Err(format!("`{}` is both an alias and a macro", name));
+            }
+            if lines.iter().all(|line| line.trim().is_empty()) {
+                return Err(format!("macro `{}` expands to nothing", name));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the value of the given key, formatted as `config set` expects it
+    pub fn get(&self, key: &str) -> Result<String, String> {
+        let value = match key {
+            "prompt" => self.prompt.clone(),
+            "date_format" => self.date_format.clone(),
+            "colors" => self.colors.to_string(),
+            "display.hide_done" => self.display.hide_done.to_string(),
+            "display.counts_only" => self.display.counts_only.to_string(),
+            "display.collapse_done" => self.display.collapse_done.to_string(),
+            "display.limit" => match self.display.limit {
+                Some(limit) => limit.to_string(),
+                None => "none".into(),
+            },
+            "storage" => self.storage.to_string(),
+            "pretty_ron" => self.pretty_ron.to_string(),
+            "autosave" => self.autosave.to_string(),
+            "sync.remote" => self.sync.remote.clone().unwrap_or_else(|| "none".into()),
+            "sync.auto_commit" => self.sync.auto_commit.to_string(),
+            key => return Err(unknown_key(key, KEYS)),
+        };
+
+        Ok(value)
+    }
+
+    /// Sets the given key to the given value.
+    /// The configuration is left untouched if the new value is invalid
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let mut config = self.clone();
+
+        match key {
+            "prompt" => config.prompt = value.into(),
+            "date_format" => config.date_format = value.into(),
+            "colors" => config.colors = parse_bool(value)?,
+            "display.hide_done" => config.display.hide_done = parse_bool(value)?,
+            "display.counts_only" => config.display.counts_only = parse_bool(value)?,
+            "display.collapse_done" => config.display.collapse_done = parse_bool(value)?,
+            "display.limit" => {
+                config.display.limit = match value {
+                    "none" => None,
+                    value => Some(value.parse().map_err(|_| {
+                        format!("expected a number of tasks or `none`, found `{}`", value)
+                    })?),
+                }
+            }
+            "storage" => {
+                config.storage = value.parse()?;
+                // Otherwise every later run would fail to open the storage, including the one
+                // setting it back
+                config.storage.check_available()?;
+            }
+            "pretty_ron" => config.pretty_ron = parse_bool(value)?,
+            "autosave" => config.autosave = parse_bool(value)?,
+            "sync.remote" => {
+                config.sync.remote = Some(value.to_string()).filter(|value| value != "none")
+            }
+            "sync.auto_commit" => config.sync.auto_commit = parse_bool(value)?,
+            key => return Err(unknown_key(key, KEYS)),
+        }
+
+        config.validate()?;
+        *self = config;
+        Ok(())
+    }
+
+    /// Defines an alias, replacing any alias or macro of the same name
+    pub fn set_alias(&mut self, name: String, expansion: String) -> Result<(), String> {
+        let mut config = self.clone();
+        config.macros.remove(&name);
+        config.aliases.insert(name, expansion);
+
+        config.validate()?;
+        *self = config;
+        Ok(())
+    }
+
+    /// Defines a macro, replacing any alias or macro of the same name
+    pub fn set_macro(&mut self, name: String, lines: Vec<String>) -> Result<(), String> {
+        let mut config = self.clone();
+        config.aliases.remove(&name);
+        config.macros.insert(name, lines);
+
+        config.validate()?;
+        *self = config;
+        Ok(())
+    }
+
+    /// Removes the alias or macro of the given name, returning whether there was one
+    pub fn remove_alias(&mut self, name: &str) -> bool {
+        self.aliases.remove(name).is_some() || self.macros.remove(name).is_some()
+    }
+}
+
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for key in KEYS {
+            // Every key in KEYS is known, so this can't fail
+            writeln!(f, "{} = {:?}", key, self.get(key).unwrap_or_default())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loading() {
+        let config =
+            Config::from_ron("(prompt: \"> \", display: (hide_done: true, limit: Some(5)))")
+                .unwrap();
+
+        assert_eq!(config.prompt, "> ");
+        assert_eq!(config.date_format, "%Y-%m-%d");
+        assert!(config.display.hide_done);
+        assert_eq!(config.display.limit, Some(5));
+    }
+
+    #[test]
+    fn unknown_keys() {
+        assert_eq!(
+            Config::from_ron("(promt: \"> \")"),
+            Err("unknown key `promt`. Did you mean `prompt`?".into())
+        );
+        assert_eq!(
+            Config::from_ron("(display: (hide: true))"),
+            Err("unknown key `display.hide`. Valid keys are: prompt, date_format, colors, display.hide_done, display.counts_only, display.collapse_done, display.limit, storage, pretty_ron, autosave, sync.remote, sync.auto_commit, aliases, macros".into())
+        );
+    }
+
+    #[test]
+    fn validation() {
+        assert!(Config::from_ron("(date_format: \"%Q\")").is_err());
+        assert!(Config::from_ron("(display: (limit: Some(0)))").is_err());
+    }
+
+    #[test]
+    fn get_and_set() {
+        let mut config = Config::default();
+
+        config.set("display.limit", "3").unwrap();
+        assert_eq!(config.get("display.limit"), Ok("3".into()));
+        config.set("display.limit", "none").unwrap();
+        assert_eq!(config.get("display.limit"), Ok("none".into()));
+
+        config.set("storage", "memory").unwrap();
+        assert_eq!(config.get("storage"), Ok("memory".into()));
+        assert_eq!(
+            Config::from_ron(&ron::ser::to_string(&config).unwrap()),
+            Ok(config.clone())
+        );
+        assert!(config.set("storage", "floppy").is_err());
+        #[cfg(not(feature = "sqlite"))]
+        assert!(config.set("storage", "sqlite").is_err());
+
+        config
+            .set("sync.remote", "git@example.com:team/todo.git")
+            .unwrap();
+        assert_eq!(
+            config.get("sync.remote"),
+            Ok("git@example.com:team/todo.git".into())
+        );
+        config.set("sync.remote", "none").unwrap();
+        assert_eq!(config.sync.remote, None);
+
+        config.set("pretty_ron", "false").unwrap();
+        assert_eq!(config.get("pretty_ron"), Ok("false".into()));
+
+        assert!(config.set("colors", "maybe").is_err());
+        assert!(config.set("date_format", "%Q").is_err());
+        assert!(config.set("date_format", "%H:%M").is_err());
+        assert_eq!(config.get("date_format"), Ok("%Y-%m-%d".into()));
+        assert!(config
+            .get("colours")
+            .unwrap_err()
+            .contains("Did you mean `colors`?"));
+    }
+
+    #[test]
+    fn migrating_and_fixing_config_files() -> Result<(), Error> {
+        let folder = std::env::temp_dir().join(format!("dodo-config-{}", std::process::id()));
+        fs::create_dir_all(&folder)?;
+        fs::write(
+            folder.join(SETTINGS_FILE_NAME),
+            "(hide_done:true,counts_only:false,collapse_done:false,limit:Some(3))",
+        )?;
+        let migrated = Config::load(&folder);
+        let reloaded = Config::load(&folder);
+
+        fs::write(
+            folder.join(CONFIG_FILE_NAME),
+            "(prompt: \"> \", promt: \"$ \", date_format: \"%Q\")",
+        )?;
+        let invalid = Config::load(&folder);
+        let unchecked = Config::load_unchecked(&folder);
+        fs::remove_dir_all(&folder)?;
+
+        let migrated = migrated?;
+        assert!(migrated.display.hide_done);
+        assert_eq!(migrated.display.limit, Some(3));
+        assert_eq!(reloaded?, migrated);
+
+        assert!(invalid.is_err());
+        let mut unchecked = unchecked?;
+        assert_eq!(unchecked.prompt, "> ");
+        assert!(unchecked.set("colors", "false").is_err());
+        unchecked.set("date_format", "%d/%m").unwrap();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,285 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use colored::Colorize;
+
+use crate::config::{self, Config};
+use crate::storage::StoredList;
+use crate::task::{Task, TaskGroup};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+/// A change between two versions of a todo list.
+/// Task references are in the `G.T` format, numbered as in the version the task is part of
+pub enum Change {
+    AddedGroup(String),
+    RemovedGroup(String),
+    AddedTask(String, Task),
+    RemovedTask(String, Task),
+    /// Arguments are (task reference, the task before, the task after)
+    ChangedTask(String, Task, Task),
+    /// Arguments are (number of archived tasks before, number of archived tasks after)
+    ArchiveResized(usize, usize),
+    /// A configuration key, alias or macro that changed, was added or was removed.
+    /// Arguments are (the setting, its value before, its value after), where `none` stands for
+    /// a setting that doesn't exist
+    ChangedSetting(String, String, String),
+}
+
+/// The changes between two versions of a todo list, as shown by `--dry-run`
+pub struct Diff {
+    pub changes: Vec<Change>,
+}
+
+/// Compares the tasks of two versions of a group. Tasks are matched by description,
+/// keeping the longest sequence of tasks whose order didn't change
+fn diff_tasks(before: (usize, &TaskGroup), after: (usize, &TaskGroup), changes: &mut Vec<Change>) {
+    let (old_no, old) = before;
+    let (new_no, new) = after;
+    let (old_len, new_len) = (old.tasks.len(), new.tasks.len());
+
+    // common[i][j] is the length of the longest common sequence of old.tasks[i..] and new.tasks[j..]
+    let mut common = vec![vec![0; new_len + 1]; old_len + 1];
+    for i in (0..old_len).rev() {
+        for j in (0..new_len).rev() {
+            common[i][j] = match old.tasks[i].description == new.tasks[j].description {
+                true => common[i + 1][j + 1] + 1,
+                false => common[i + 1][j].max(common[i][j + 1]),
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < old_len || j < new_len {
+        let old_ref = || format!("{}.{}", old_no, i + 1);
+        let new_ref = || format!("{}.{}", new_no, j + 1);
+
+        if i < old_len && j < new_len && old.tasks[i].description == new.tasks[j].description {
+            if old.tasks[i] != new.tasks[j] {
+                changes.push(Change::ChangedTask(
+                    new_ref(),
+                    old.tasks[i].clone(),
+                    new.tasks[j].clone(),
+                ));
+            }
+            i += 1;
+            j += 1;
+        } else if i < old_len && (j == new_len || common[i + 1][j] >= common[i][j + 1]) {
+            changes.push(Change::RemovedTask(old_ref(), old.tasks[i].clone()));
+            i += 1;
+        } else {
+            changes.push(Change::AddedTask(new_ref(), new.tasks[j].clone()));
+            j += 1;
+        }
+    }
+}
+
+/// Every setting of a configuration by name, including its aliases and macros
+fn settings(config: &Config) -> BTreeMap<String, String> {
+    let mut settings: BTreeMap<String, String> = config::KEYS
+        .iter()
+        .map(|key| (key.to_string(), config.get(key).unwrap_or_default()))
+        .collect();
+    settings.extend(
+        config
+            .aliases
+            .iter()
+            .map(|(name, expansion)| (format!("alias {}", name), expansion.clone())),
+    );
+    settings.extend(
+        config
+            .macros
+            .iter()
+            .map(|(name, lines)| (format!("macro {}", name), lines.join(" ; "))),
+    );
+    settings
+}
+
+impl Diff {
+    /// Adds the settings that differ between two versions of the configuration
+    pub fn with_config(mut self, before: &Config, after: &Config) -> Self {
+        let (before, after) = (settings(before), settings(after));
+        let names: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+        let value = |settings: &BTreeMap<String, String>, name: &str| {
+            settings.get(name).cloned().unwrap_or_else(|| "none".into())
+        };
+
+        for name in names {
+            let (old, new) = (value(&before, name), value(&after, name));
+            if old != new || before.contains_key(name) != after.contains_key(name) {
+                self.changes
+                    .push(Change::ChangedSetting(name.clone(), old, new));
+            }
+        }
+        self
+    }
+
+    /// Compares two versions of a todo list. Groups are matched by name
+    pub fn between(before: &StoredList, after: &StoredList) -> Self {
+        let mut changes = vec![];
+        let mut matched = vec![false; after.task_groups.len()];
+
+        for (old_idx, old) in before.task_groups.iter().enumerate() {
+            let new_idx = after
+                .task_groups
+                .iter()
+                .enumerate()
+                .position(|(new_idx, new)| !matched[new_idx] && new.name == old.name);
+
+            match new_idx {
+                Some(new_idx) => {
+                    matched[new_idx] = true;
+                    let new = &after.task_groups[new_idx];
+                    diff_tasks((old_idx + 1, old), (new_idx + 1, new), &mut changes);
+                }
+                None => {
+                    changes.push(Change::RemovedGroup(old.name.clone()));
+                    changes.extend(old.tasks.iter().enumerate().map(|(task_idx, task)| {
+                        Change::RemovedTask(
+                            format!("{}.{}", old_idx + 1, task_idx + 1),
+                            task.clone(),
+                        )
+                    }));
+                }
+            }
+        }
+
+        for (new_idx, new) in after.task_groups.iter().enumerate() {
+            if matched[new_idx] {
+                continue;
+            }
+            changes.push(Change::AddedGroup(new.name.clone()));
+            changes.extend(new.tasks.iter().enumerate().map(|(task_idx, task)| {
+                Change::AddedTask(format!("{}.{}", new_idx + 1, task_idx + 1), task.clone())
+            }));
+        }
+
+        let archived = (before.archive.entries.len(), after.archive.entries.len());
+        if archived.0 != archived.1 {
+            changes.push(Change::ArchiveResized(archived.0, archived.1));
+        }
+
+        Self { changes }
+    }
+}
+
+impl Change {
+    /// The line showing the change, before it's coloured
+    pub fn line(&self) -> String {
+        match self {
+            Change::AddedGroup(name) => format!("+ group {}", name),
+            Change::RemovedGroup(name) => format!("- group {}", name),
+            Change::AddedTask(task_ref, task) => format!("+ {} {}", task_ref, task),
+            Change::RemovedTask(task_ref, task) => format!("- {} {}", task_ref, task),
+            Change::ChangedTask(task_ref, old, new) => {
+                format!("~ {} {} -> {}", task_ref, old, new)
+            }
+            Change::ArchiveResized(old, new) if new > old => {
+                format!("+ {} task(s) archived", new - old)
+            }
+            Change::ArchiveResized(old, new) => {
+                format!("- {} task(s) restored from the archive", old - new)
+            }
+            Change::ChangedSetting(name, old, new) => {
+                format!("~ config {}: {} -> {}", name, old, new)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.changes.is_empty() {
+            return writeln!(f, "No changes.");
+        }
+
+        for change in &self.changes {
+            let line = change.line();
+            let line = match change {
+                Change::AddedGroup(_) | Change::AddedTask(..) => line.green(),
+                Change::RemovedGroup(_) | Change::RemovedTask(..) => line.red(),
+                Change::ChangedTask(..) | Change::ChangedSetting(..) => line.yellow(),
+                Change::ArchiveResized(old, new) if new > old => line.green(),
+                Change::ArchiveResized(..) => line.red(),
+            };
+            writeln!(f, "{}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::*;
+    use crate::command::Command;
+    use crate::errors;
+    use crate::todolist::TodoList;
+
+    fn task(description: &str) -> Task {
+        Task {
+            description: description.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn changes() -> Result<(), errors::Error> {
+        let before = TodoList::try_from(vec![
+            TaskGroup {
+                name: "Work".into(),
+                tasks: vec![task("Write the report"), task("Deploy"), task("Review")],
+            },
+            TaskGroup {
+                name: "Chores".into(),
+                tasks: vec![task("Do the dishes")],
+            },
+        ])?;
+
+        let mut after = before.clone();
+        after.evaluate(Command::RemoveTask(1, 2));
+        after.evaluate(Command::AddTask("Celebrate".into(), 1));
+        after.evaluate(Command::RemoveGroup(2));
+        after.evaluate(Command::AddGroup("Uni".into()));
+        after.task_groups[0].tasks[1].is_done = true;
+
+        let diff = Diff::between(&before.stored(), &after.stored());
+        assert_eq!(
+            diff.changes.iter().map(Change::line).collect::<Vec<_>>(),
+            [
+                "- 1.2 [ ] - Deploy",
+                "~ 1.2 [ ] - Review -> [x] - Review",
+                "+ 1.3 [ ] - Celebrate",
+                "- group Chores",
+                "- 2.1 [ ] - Do the dishes",
+                "+ group Uni",
+            ]
+        );
+        assert_eq!(
+            Diff::between(&before.stored(), &before.stored()).to_string(),
+            "No changes.\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn config_changes() {
+        let before = Config::default();
+        let mut after = before.clone();
+        after.set("date_format", "%d/%m").unwrap();
+        after.set_alias("x".into(), "done".into()).unwrap();
+
+        let diff = Diff { changes: vec![] }.with_config(&before, &after);
+        assert_eq!(
+            diff.changes.iter().map(Change::line).collect::<Vec<_>>(),
+            [
+                "~ config alias x: none -> done",
+                "~ config date_format: %Y-%m-%d -> %d/%m",
+            ]
+        );
+    }
+}
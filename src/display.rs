@@ -0,0 +1,196 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    fmt::{self, Write},
+    str::FromStr,
+};
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::filter::Filter;
+use crate::task::Task;
+
+/// Options that change how the todo list is displayed
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct DisplayOptions {
+    /// Show when each task was created, completed and last modified
+    pub show_timestamps: bool,
+    /// Only show the tasks matching this filter
+    pub filter: Option<Filter>,
+    /// Show the tasks in this order instead of the order they're stored in
+    pub sort: Option<SortKey>,
+    /// Reverse the sorting order
+    pub reverse: bool,
+    /// Show every task in a single list instead of under their groups
+    pub flat: bool,
+    /// Hide tasks that are done.
+    /// None means the persisted display setting is used, as with the options below
+    pub hide_done: Option<bool>,
+    /// Only show how many tasks are pending in each group
+    pub counts_only: Option<bool>,
+    /// Show groups whose tasks are all done as a single line
+    pub collapse_done: Option<bool>,
+    /// Show at most this many tasks per group. Some(0) means no limit
+    pub limit: Option<usize>,
+    /// How dates are shown, in strftime format. None means the configured format is used
+    pub date_format: Option<String>,
+}
+
+/// The format dates are shown in when none was configured
+pub const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Writes a date in the given strftime format, failing if the format needs more than a date,
+/// such as `%H:%M`
+pub fn try_format_date(date: NaiveDate, date_format: &str) -> Result<String, fmt::Error> {
+    let mut formatted = String::new();
+    write!(formatted, "{}", date.format(date_format))?;
+    Ok(formatted)
+}
+
+/// Writes a date in the given strftime format, or in the default one if it can't be written that way
+pub fn format_date(date: NaiveDate, date_format: &str) -> String {
+    try_format_date(date, date_format)
+        .unwrap_or_else(|_| date.format(DEFAULT_DATE_FORMAT).to_string())
+}
+
+impl DisplayOptions {
+    /// Fills in the options that weren't given with the user's configuration
+    pub fn with_defaults(mut self, config: &Config) -> Self {
+        let settings = &config.display;
+        self.hide_done = self.hide_done.or(Some(settings.hide_done));
+        self.counts_only = self.counts_only.or(Some(settings.counts_only));
+        self.collapse_done = self.collapse_done.or(Some(settings.collapse_done));
+        self.limit = self.limit.or(settings.limit);
+        self.date_format = self
+            .date_format
+            .or_else(|| Some(config.date_format.clone()));
+        self
+    }
+
+    /// Parses a display option as given to `show` or `display`.
+    /// Returns None if the word isn't a display option.
+    pub fn apply(&mut self, word: &str) -> Option<Result<(), String>> {
+        match word {
+            "dates" => self.show_timestamps = true,
+            "flat" => self.flat = true,
+            "reverse" => self.reverse = true,
+            "hide-done" => self.hide_done = Some(true),
+            "all" | "show-done" => self.hide_done = Some(false),
+            "counts" => self.counts_only = Some(true),
+            "collapse" => self.collapse_done = Some(true),
+            "expand" => self.collapse_done = Some(false),
+            word if word.starts_with("sort:") => {
+                return Some(
+                    word["sort:".len()..]
+                        .parse()
+                        .map(|key| self.sort = Some(key)),
+                );
+            }
+            word if word.starts_with("limit:") => {
+                let limit = word["limit:".len()..]
+                    .parse()
+                    .map(|limit| self.limit = Some(limit))
+                    .map_err(|_| format!("expected a number of tasks, found `{}`", word));
+                return Some(limit);
+            }
+            _ => return None,
+        }
+
+        Some(Ok(()))
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(default)]
+/// The display options used by default, stored in the `display` section of the configuration
+pub struct DisplaySettings {
+    pub hide_done: bool,
+    pub counts_only: bool,
+    pub collapse_done: bool,
+    pub limit: Option<usize>,
+}
+
+impl DisplaySettings {
+    /// Persists the given display options as the new defaults
+    pub fn update(&mut self, options: &DisplayOptions) {
+        self.hide_done = options.hide_done.unwrap_or(self.hide_done);
+        self.counts_only = options.counts_only.unwrap_or(self.counts_only);
+        self.collapse_done = options.collapse_done.unwrap_or(self.collapse_done);
+        if let Some(limit) = options.limit {
+            self.limit = Some(limit).filter(|&limit| limit > 0);
+        }
+    }
+}
+
+impl std::fmt::Display for DisplaySettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "hide-done: {}", self.hide_done)?;
+        writeln!(f, "counts: {}", self.counts_only)?;
+        writeln!(f, "collapse: {}", self.collapse_done)?;
+        match self.limit {
+            Some(limit) => writeln!(f, "limit: {}", limit),
+            None => writeln!(f, "limit: none"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+/// What tasks can be sorted by when displayed
+pub enum SortKey {
+    /// Oldest first
+    Created,
+    /// Soonest due first, tasks without a due date last
+    Due,
+    /// Most important first, tasks without a priority last
+    Priority,
+    /// By description, ignoring case
+    Alphabetical,
+    /// Pending tasks first
+    Completion,
+}
+
+impl SortKey {
+    pub fn compare(self, left: &Task, right: &Task) -> Ordering {
+        /// Orders Somes by their values and Nones after them
+        fn nones_last<T: Ord>(left: Option<T>, right: Option<T>) -> Ordering {
+            match (left, right) {
+                (Some(left), Some(right)) => left.cmp(&right),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+        }
+
+        match self {
+            SortKey::Created => nones_last(left.created_at, right.created_at),
+            SortKey::Due => nones_last(left.due_date, right.due_date),
+            SortKey::Priority => {
+                nones_last(left.priority.map(Reverse), right.priority.map(Reverse))
+            }
+            SortKey::Alphabetical => left
+                .description
+                .to_lowercase()
+                .cmp(&right.description.to_lowercase()),
+            SortKey::Completion => left.is_done.cmp(&right.is_done),
+        }
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(key: &str) -> Result<Self, Self::Err> {
+        match key {
+            "created" | "creation" => Ok(SortKey::Created),
+            "due" => Ok(SortKey::Due),
+            "priority" => Ok(SortKey::Priority),
+            "alpha" | "alphabetical" | "name" => Ok(SortKey::Alphabetical),
+            "done" | "completion" | "status" => Ok(SortKey::Completion),
+            key => Err(format!(
+                "cannot sort by `{}`. Expected `created`, `due`, `priority`, `alpha` or `done`",
+                key
+            )),
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::{collections::HashSet, path::PathBuf};
+use std::{collections::HashSet, path::Path};
 
 use colored::Colorize;
 
@@ -7,7 +7,7 @@ use rustyline::Context;
 use rustyline::{self, error::ReadlineError};
 use rustyline_derive::{Completer, Helper, Highlighter, Validator};
 
-static HISTORY_FILE_NAME: &'static str = "ron_history";
+static HISTORY_FILE_NAME: &str = "ron_history";
 
 impl Hint for CommandHint {
     fn display(&self) -> &str {
@@ -30,6 +30,16 @@ fn editor_hints() -> HashSet<CommandHint> {
     // set.insert(CommandHint::new("remove task",  "remove t"));
     set.insert(CommandHint::new("remove", "r"));
     set.insert(CommandHint::new("help", "h"));
+    set.insert(CommandHint::new("clean", "c"));
+    set.insert(CommandHint::new("find", "f"));
+    set.insert(CommandHint::new("query save", "q"));
+    set.insert(CommandHint::new("display", "d"));
+    set.insert(CommandHint::new("stats", "st"));
+    set.insert(CommandHint::new("config set", "co"));
+    set.insert(CommandHint::new("alias", "al"));
+    set.insert(CommandHint::new("macro", "m"));
+    set.insert(CommandHint::new("restore", "res"));
+    set.insert(CommandHint::new("show archive", "show a"));
     set
 }
 
@@ -88,17 +98,18 @@ pub struct Editor {
 }
 
 impl Editor {
-    /// Returns a new rustyline::Editor with history loaded in (if it exists)
-    pub fn new(config_path: &PathBuf) -> Self {
+    /// Returns a new rustyline::Editor with history loaded in (if it exists).
+    /// History is only kept for lists that have a config folder
+    pub fn new(config_path: Option<&Path>) -> Self {
         let mut inner = rustyline::Editor::<EditorHinter>::new();
         let hinter = EditorHinter {
             hints: editor_hints(),
         };
         inner.set_helper(Some(hinter));
-        if inner
-            .load_history(&config_path.join(HISTORY_FILE_NAME))
-            .is_err()
-        {}
+        // It's fine if there's no history to be loaded yet
+        if let Some(config_path) = config_path {
+            let _ = inner.load_history(&config_path.join(HISTORY_FILE_NAME));
+        }
 
         Self { inner }
     }
@@ -106,6 +117,8 @@ impl Editor {
     pub fn read_line(&mut self, prompt: &str) -> Result<String, ReadlineError> {
         match self.inner.readline(prompt) {
             Ok(line) => {
+                // When stdin isn't a terminal, the line ending is kept
+                let line = line.trim_end_matches(&['\n', '\r'][..]).to_string();
                 self.inner.add_history_entry(line.as_str());
                 Ok(line)
             }
@@ -113,7 +126,11 @@ impl Editor {
         }
     }
 
-    pub fn save_history(&mut self, path: &PathBuf) {
+    pub fn save_history(&mut self, path: Option<&Path>) {
+        let path = match path {
+            Some(path) => path,
+            None => return,
+        };
         if let Err(err) = self.inner.save_history(&path.join(HISTORY_FILE_NAME)) {
             eprintln!("{}: problem saving history: {:?}", "warning".yellow(), err);
         }
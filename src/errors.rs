@@ -1,8 +1,7 @@
 use std::{fmt, io, path::PathBuf};
 
-use ron;
-use clap;
 #[allow(dead_code)]
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug)]
 pub enum Error {
     NoValidHomeDirFound,
@@ -11,7 +10,13 @@ pub enum Error {
     FileSystemError(io::Error),
     RonError(ron::error::ErrorCode),
     IoError(std::io::Error),
-    ClapError(clap::Error)
+    ClapError(clap::Error),
+    InvalidQuery(String),
+    InvalidConfig(String),
+    StorageError(String),
+    ExternalChanges,
+    WatchError(String),
+    SyncError(String),
 }
 
 impl fmt::Display for Error {
@@ -43,6 +48,24 @@ impl fmt::Display for Error {
             Error::ParseIntError => {
                 write!(f, "Could not parse integer")
             }
+            Error::InvalidQuery(reason) => {
+                write!(f, "Invalid query: {}", reason)
+            }
+            Error::InvalidConfig(reason) => {
+                write!(f, "Invalid configuration: {}", reason)
+            }
+            Error::StorageError(reason) => {
+                write!(f, "Could not load or save the list: {}", reason)
+            }
+            Error::ExternalChanges => {
+                write!(f, "The list was changed elsewhere since it was loaded")
+            }
+            Error::WatchError(reason) => {
+                write!(f, "Could not watch the list for changes: {}", reason)
+            }
+            Error::SyncError(reason) => {
+                write!(f, "Could not sync the list: {}", reason)
+            }
         }
     }
 }
@@ -59,6 +82,13 @@ impl From<std::io::Error> for Error {
     }
 }
 
+#[cfg(feature = "sqlite")]
+impl From<rusqlite::Error> for Error {
+    fn from(err: rusqlite::Error) -> Self {
+        Self::StorageError(err.to_string())
+    }
+}
+
 impl From<clap::Error> for Error {
     fn from(err: clap::Error) -> Self {
         Self::ClapError(err)
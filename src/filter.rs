@@ -0,0 +1,374 @@
+use std::collections::BTreeMap;
+
+use chrono::{Duration, NaiveDate};
+
+use crate::task::{self, Priority, Task, TaskGroup};
+
+/// How many saved queries may refer to one another before we assume there's a cycle
+const MAX_SAVED_QUERY_DEPTH: usize = 8;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+impl Comparison {
+    fn holds<T: Ord>(self, left: T, right: T) -> bool {
+        match self {
+            Comparison::Less => left < right,
+            Comparison::LessOrEqual => left <= right,
+            Comparison::Equal => left == right,
+            Comparison::GreaterOrEqual => left >= right,
+            Comparison::Greater => left > right,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+/// A date in a query, either fixed or relative to the day the query runs
+pub enum DateOperand {
+    /// e.g. `2021-03-14`
+    Absolute(NaiveDate),
+    /// A number of days from today, e.g. `7d`, `today` or `tomorrow`
+    DaysFromToday(i64),
+}
+
+impl DateOperand {
+    /// The date the operand stands for, clamped to the dates that can be represented
+    fn resolve(self, today: NaiveDate) -> NaiveDate {
+        match self {
+            DateOperand::Absolute(date) => date,
+            DateOperand::DaysFromToday(days) => today
+                .checked_add_signed(Duration::days(days))
+                .unwrap_or(if days > 0 {
+                    NaiveDate::MAX
+                } else {
+                    NaiveDate::MIN
+                }),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+/// A query over the tasks of the todo list, as given to `show`.
+///
+/// Terms separated by spaces must all match, `or` matches either side
+/// and `not` (or a leading `!`) negates the following term. Examples:
+///
+///     pending group:Uni due<7d +backend priority>=high
+///     done or +urgent
+///     not group:Chores @weekly
+pub enum Filter {
+    /// `pending`: tasks that are not done
+    Pending,
+    /// `done`: tasks that are done
+    Done,
+    /// `overdue`: pending tasks whose due date has passed
+    Overdue,
+    /// `group:NAME` or `group:N`: tasks of the given group
+    Group(String),
+    /// `+tag`: tasks with the given tag
+    Tag(String),
+    /// `due<7d`, `due>=2021-03-14`, `due:today`: tasks whose due date compares as given
+    Due(Comparison, DateOperand),
+    /// `priority>=high`, `priority:low`: tasks whose priority compares as given
+    Priority(Comparison, Priority),
+    /// Any other word: tasks whose description contains it, ignoring case
+    Text(String),
+    /// `@name`: the query saved under the given name
+    Saved(String),
+    Not(Box<Filter>),
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+}
+
+/// Splits `key<op>value` terms such as `due<=7d` into their parts
+fn split_comparison<'a>(word: &'a str, key: &str) -> Option<(Comparison, &'a str)> {
+    let rest = word.strip_prefix(key)?;
+    let operators = [
+        ("<=", Comparison::LessOrEqual),
+        (">=", Comparison::GreaterOrEqual),
+        ("<", Comparison::Less),
+        (">", Comparison::Greater),
+        ("=", Comparison::Equal),
+        (":", Comparison::Equal),
+    ];
+
+    operators
+        .iter()
+        .find_map(|(op, comparison)| rest.strip_prefix(op).map(|value| (*comparison, value)))
+}
+
+fn parse_date_operand(value: &str) -> Result<DateOperand, String> {
+    let days = value
+        .strip_suffix('d')
+        .and_then(|days| days.parse::<i64>().ok());
+
+    // Beyond this many days, no date could be reached from any day
+    let max_days = NaiveDate::MAX
+        .signed_duration_since(NaiveDate::MIN)
+        .num_days();
+
+    match (value, days) {
+        (_, Some(days)) if !(-max_days..=max_days).contains(&days) => {
+            Err(format!("`{}` is too far from today", value))
+        }
+        (_, Some(days)) => Ok(DateOperand::DaysFromToday(days)),
+        ("today", _) => Ok(DateOperand::DaysFromToday(0)),
+        ("tomorrow", _) => Ok(DateOperand::DaysFromToday(1)),
+        ("yesterday", _) => Ok(DateOperand::DaysFromToday(-1)),
+        (value, _) => task::parse_date(value).map(DateOperand::Absolute),
+    }
+}
+
+fn parse_term(word: &str) -> Result<Filter, String> {
+    if let Some(negated) = word.strip_prefix('!').filter(|rest| !rest.is_empty()) {
+        return Ok(Filter::Not(Box::new(parse_term(negated)?)));
+    }
+
+    if let Some((comparison, value)) = split_comparison(word, "due") {
+        return Ok(Filter::Due(comparison, parse_date_operand(value)?));
+    }
+
+    if let Some((comparison, value)) = split_comparison(word, "priority") {
+        return Ok(Filter::Priority(comparison, value.parse()?));
+    }
+
+    if let Some(group) = word.strip_prefix("group:") {
+        return Ok(Filter::Group(group.into()));
+    }
+
+    let filter = match word {
+        "pending" => Filter::Pending,
+        "done" => Filter::Done,
+        "overdue" => Filter::Overdue,
+        word if word.len() > 1 && word.starts_with('+') => Filter::Tag(word[1..].into()),
+        word if word.len() > 1 && word.starts_with('@') => Filter::Saved(word[1..].into()),
+        word => Filter::Text(word.to_lowercase()),
+    };
+
+    Ok(filter)
+}
+
+/// Parses a query from the words following `show`
+pub fn parse(words: &[&str]) -> Result<Filter, String> {
+    let mut alternatives = vec![];
+    let mut terms = vec![];
+    let mut negate_next = false;
+
+    for word in words {
+        match *word {
+            "or" | "OR" => {
+                if terms.is_empty() || negate_next {
+                    return Err("`or` must be placed between two terms".into());
+                }
+                alternatives.push(Filter::And(std::mem::take(&mut terms)));
+            }
+            "not" | "NOT" => negate_next = !negate_next,
+            word => {
+                let term = parse_term(word)?;
+                terms.push(match negate_next {
+                    true => Filter::Not(Box::new(term)),
+                    false => term,
+                });
+                negate_next = false;
+            }
+        }
+    }
+
+    if terms.is_empty() || negate_next {
+        return Err("the query ends unexpectedly".into());
+    }
+    alternatives.push(Filter::And(terms));
+
+    let simplify = |filter: Filter| match filter {
+        Filter::And(mut terms) if terms.len() == 1 => terms.remove(0),
+        filter => filter,
+    };
+
+    let mut alternatives: Vec<Filter> = alternatives.into_iter().map(simplify).collect();
+    match alternatives.len() {
+        1 => Ok(alternatives.remove(0)),
+        _ => Ok(Filter::Or(alternatives)),
+    }
+}
+
+impl Filter {
+    /// Replaces references to saved queries (`@name`) by the queries themselves
+    pub fn resolve(self, saved_queries: &BTreeMap<String, String>) -> Result<Filter, String> {
+        self.resolve_with_depth(saved_queries, 0)
+    }
+
+    fn resolve_with_depth(
+        self,
+        saved_queries: &BTreeMap<String, String>,
+        depth: usize,
+    ) -> Result<Filter, String> {
+        let resolve_all = |filters: Vec<Filter>| -> Result<Vec<Filter>, String> {
+            filters
+                .into_iter()
+                .map(|filter| filter.resolve_with_depth(saved_queries, depth))
+                .collect()
+        };
+
+        match self {
+            Filter::Saved(name) => {
+                if depth >= MAX_SAVED_QUERY_DEPTH {
+                    return Err(format!("saved query `{}` refers to itself", name));
+                }
+                let query = saved_queries
+                    .get(&name)
+                    .ok_or_else(|| format!("there's no saved query named `{}`", name))?;
+                let words: Vec<&str> = query.split(' ').filter(|x| !x.is_empty()).collect();
+                parse(&words)?.resolve_with_depth(saved_queries, depth + 1)
+            }
+            Filter::Not(filter) => Ok(Filter::Not(Box::new(
+                filter.resolve_with_depth(saved_queries, depth)?,
+            ))),
+            Filter::And(filters) => Ok(Filter::And(resolve_all(filters)?)),
+            Filter::Or(filters) => Ok(Filter::Or(resolve_all(filters)?)),
+            filter => Ok(filter),
+        }
+    }
+
+    /// Whether the given task, the `task_no`-th of the `group_no`-th group, matches this filter.
+    /// Saved queries must have been resolved beforehand.
+    pub fn matches(
+        &self,
+        group_no: usize,
+        group: &TaskGroup,
+        task: &Task,
+        today: NaiveDate,
+    ) -> bool {
+        match self {
+            Filter::Pending => !task.is_done,
+            Filter::Done => task.is_done,
+            Filter::Overdue => !task.is_done && task.due_date.is_some_and(|due| due < today),
+            Filter::Group(name) => match name.parse::<usize>() {
+                Ok(number) => number == group_no,
+                Err(_) => group.name.to_lowercase() == name.to_lowercase(),
+            },
+            Filter::Tag(tag) => task.tags.iter().any(|task_tag| task_tag == tag),
+            Filter::Due(comparison, date) => task
+                .due_date
+                .is_some_and(|due| comparison.holds(due, date.resolve(today))),
+            Filter::Priority(comparison, priority) => task
+                .priority
+                .is_some_and(|task_priority| comparison.holds(task_priority, *priority)),
+            Filter::Text(text) => task.description.to_lowercase().contains(text),
+            Filter::Saved(_) => false,
+            Filter::Not(filter) => !filter.matches(group_no, group, task, today),
+            Filter::And(filters) => filters
+                .iter()
+                .all(|filter| filter.matches(group_no, group, task, today)),
+            Filter::Or(filters) => filters
+                .iter()
+                .any(|filter| filter.matches(group_no, group, task, today)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(query: &str) -> Result<Filter, String> {
+        let words: Vec<&str> = query.split(' ').collect();
+        parse(&words)
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn parsing() {
+        assert_eq!(
+            parse_str("pending group:Uni due<7d +backend priority>=high"),
+            Ok(Filter::And(vec![
+                Filter::Pending,
+                Filter::Group("Uni".into()),
+                Filter::Due(Comparison::Less, DateOperand::DaysFromToday(7)),
+                Filter::Tag("backend".into()),
+                Filter::Priority(Comparison::GreaterOrEqual, Priority::High),
+            ]))
+        );
+
+        assert_eq!(
+            parse_str("done or not +urgent"),
+            Ok(Filter::Or(vec![
+                Filter::Done,
+                Filter::Not(Box::new(Filter::Tag("urgent".into()))),
+            ]))
+        );
+
+        assert_eq!(
+            parse_str("!@weekly"),
+            Ok(Filter::Not(Box::new(Filter::Saved("weekly".into()))))
+        );
+        assert!(parse_str("or done").is_err());
+        assert!(parse_str("priority>urgent").is_err());
+        assert!(parse_str("due<99999999999d").is_err());
+        assert!(parse_str("due>-9223372036854775808d").is_err());
+        assert!(parse_str("done not").is_err());
+    }
+
+    #[test]
+    fn matching() {
+        let today = date(2021, 3, 1);
+        let group = TaskGroup {
+            name: "Uni".into(),
+            tasks: vec![],
+        };
+        let task = Task {
+            description: "Finish the Compilers project".into(),
+            due_date: Some(date(2021, 3, 5)),
+            priority: Some(Priority::High),
+            tags: vec!["backend".into()],
+            ..Default::default()
+        };
+
+        let matches = |query: &str| parse_str(query).unwrap().matches(1, &group, &task, today);
+
+        assert!(matches(
+            "pending group:uni due<7d +backend priority>=medium"
+        ));
+        assert!(matches("group:1 compilers"));
+        assert!(matches("done or due=2021-03-05"));
+        assert!(!matches("due<3d"));
+        // Days past the last date that can be represented stand for that date
+        assert!(matches("due<150000000d"));
+        assert!(!matches("due<-150000000d"));
+        assert!(!matches("overdue"));
+        assert!(!matches("not +backend"));
+    }
+
+    #[test]
+    fn saved_queries() {
+        let mut saved_queries = BTreeMap::new();
+        saved_queries.insert("urgent".to_string(), "pending priority:high".to_string());
+        saved_queries.insert("loop".to_string(), "@loop".to_string());
+
+        assert_eq!(
+            parse_str("@urgent +backend")
+                .unwrap()
+                .resolve(&saved_queries),
+            Ok(Filter::And(vec![
+                Filter::And(vec![
+                    Filter::Pending,
+                    Filter::Priority(Comparison::Equal, Priority::High)
+                ]),
+                Filter::Tag("backend".into()),
+            ]))
+        );
+        assert!(parse_str("@loop").unwrap().resolve(&saved_queries).is_err());
+        assert!(parse_str("@missing")
+            .unwrap()
+            .resolve(&saved_queries)
+            .is_err());
+    }
+}
@@ -0,0 +1,354 @@
+use chrono::{DateTime, Local};
+
+use super::{start_of_day, ConversionOptions, DEFAULT_GROUP};
+use crate::recurrence::Recurrence;
+use crate::task::{self, Task, TaskGroup};
+
+/// Every field written for each task, in order. Their columns are named after them
+/// unless the options map them to other headers
+pub const FIELDS: &[&str] = &[
+    "group",
+    "index",
+    "description",
+    "done",
+    "created",
+    "completed",
+    "modified",
+    "due",
+    "recurrence",
+    "priority",
+    "tags",
+];
+
+/// The header of the column holding the given field
+fn header<'a>(field: &'a str, options: &'a ConversionOptions) -> &'a str {
+    options
+        .columns
+        .get(field)
+        .map(String::as_str)
+        .unwrap_or(field)
+}
+
+/// Writes one row per task, with a header row naming the columns
+pub fn export(task_groups: &[TaskGroup], options: &ConversionOptions) -> Result<String, String> {
+    let mut writer = ::csv::WriterBuilder::new()
+        .delimiter(options.delimiter)
+        .from_writer(vec![]);
+
+    let headers: Vec<&str> = FIELDS.iter().map(|field| header(field, options)).collect();
+    writer
+        .write_record(&headers)
+        .map_err(|err| err.to_string())?;
+
+    let time =
+        |time: &Option<DateTime<Local>>| time.map(|time| time.to_rfc3339()).unwrap_or_default();
+    for group in task_groups {
+        for (task_idx, task) in group.tasks.iter().enumerate() {
+            let row = [
+                group.name.clone(),
+                (task_idx + 1).to_string(),
+                task.description.clone(),
+                task.is_done.to_string(),
+                time(&task.created_at),
+                time(&task.completed_at),
+                time(&task.modified_at),
+                task.due_date
+                    .map(|due| due.format("%Y-%m-%d").to_string())
+                    .unwrap_or_default(),
+                task.recurrence
+                    .as_ref()
+                    .map(Recurrence::token)
+                    .unwrap_or_default(),
+                task.priority
+                    .map(|priority| priority.to_string())
+                    .unwrap_or_default(),
+                task.tags.join(" "),
+            ];
+            writer.write_record(&row).map_err(|err| err.to_string())?;
+        }
+    }
+
+    let contents = writer.into_inner().map_err(|err| err.to_string())?;
+    String::from_utf8(contents).map_err(|err| err.to_string())
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "y" | "x" | "1" | "done" => Ok(true),
+        "false" | "no" | "n" | "0" | "" | "pending" => Ok(false),
+        _ => Err(format!("`{}` is not a valid `done` value", value)),
+    }
+}
+
+/// Parses timestamps written as RFC 3339, or as plain dates
+fn parse_time(value: &str) -> Result<DateTime<Local>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Local));
+    }
+
+    task::parse_date(value)
+        .ok()
+        .and_then(start_of_day)
+        .ok_or_else(|| format!("`{}` is not a valid date", value))
+}
+
+/// Builds a task out of the fields of a row.
+/// `field` returns the value of the given field, or None if its cell is empty or missing
+fn parse_row<'a>(field: impl Fn(&str) -> Option<&'a str>) -> Result<Task, String> {
+    let column = |name: &str, err: String| format!("column `{}`: {}", name, err);
+
+    let mut task = Task {
+        description: field("description").unwrap_or_default().into(),
+        ..Default::default()
+    };
+
+    if let Some(value) = field("done") {
+        task.is_done = parse_bool(value).map_err(|err| column("done", err))?;
+    }
+    for (name, time) in [
+        ("created", &mut task.created_at),
+        ("completed", &mut task.completed_at),
+        ("modified", &mut task.modified_at),
+    ] {
+        if let Some(value) = field(name) {
+            *time = Some(parse_time(value).map_err(|err| column(name, err))?);
+        }
+    }
+    if let Some(value) = field("due") {
+        task.due_date = Some(task::parse_date(value).map_err(|err| column("due", err))?);
+    }
+    if let Some(value) = field("recurrence") {
+        // Both `every:3d` and `3d` are accepted
+        let recurrence = Recurrence::from_token(value).unwrap_or_else(|| value.parse());
+        task.recurrence = Some(recurrence.map_err(|err| column("recurrence", err))?);
+    }
+    if let Some(value) = field("priority") {
+        task.priority = Some(value.parse().map_err(|err| column("priority", err))?);
+    }
+
+    task.tags = field("tags")
+        .unwrap_or_default()
+        .split(|ch: char| ch == ',' || ch.is_whitespace())
+        .map(|tag| tag.trim_start_matches('+'))
+        .filter(|tag| !tag.is_empty())
+        .map(String::from)
+        .collect();
+
+    Ok(task)
+}
+
+/// Reads the rows of a CSV file with a header row. Columns are found by header, ignoring case,
+/// and may be in any order. Only `description` is required: tasks without a group column end up
+/// in the default group, and the `index` column is ignored as tasks are kept in file order.
+/// Every invalid row is reported, each on its own line.
+pub fn import(contents: &str, options: &ConversionOptions) -> Result<Vec<TaskGroup>, String> {
+    let mut reader = ::csv::ReaderBuilder::new()
+        .delimiter(options.delimiter)
+        .flexible(true)
+        .from_reader(contents.as_bytes());
+
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|err| err.to_string())?
+        .iter()
+        .map(|header| header.trim().to_lowercase())
+        .collect();
+    let position = |field: &str| {
+        let header = header(field, options).to_lowercase();
+        headers.iter().position(|found| *found == header)
+    };
+
+    for (field, header) in &options.columns {
+        if position(field).is_none() {
+            return Err(format!("there's no `{}` column for `{}`", header, field));
+        }
+    }
+    let positions: Vec<(&str, usize)> = FIELDS
+        .iter()
+        .filter_map(|&field| Some((field, position(field)?)))
+        .collect();
+    if !positions.iter().any(|&(field, _)| field == "description") {
+        return Err(format!(
+            "there's no `{}` column",
+            header("description", options)
+        ));
+    }
+
+    let mut task_groups: Vec<TaskGroup> = vec![];
+    let mut errors = vec![];
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(err) => {
+                errors.push(err.to_string());
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |position| position.line());
+
+        let field = |name: &str| {
+            let &(_, idx) = positions.iter().find(|&&(field, _)| field == name)?;
+            record
+                .get(idx)
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+        };
+        if record.iter().all(|value| value.trim().is_empty()) {
+            continue;
+        }
+
+        let task = match parse_row(field) {
+            Ok(task) => task,
+            Err(err) => {
+                errors.push(format!("line {}: {}", line, err));
+                continue;
+            }
+        };
+
+        let group_name = field("group").unwrap_or(DEFAULT_GROUP);
+        match task_groups
+            .iter_mut()
+            .find(|group| group.name == group_name)
+        {
+            Some(group) => group.tasks.push(task),
+            None => task_groups.push(TaskGroup {
+                name: group_name.into(),
+                tasks: vec![task],
+            }),
+        }
+    }
+
+    match errors.is_empty() {
+        true => Ok(task_groups),
+        false => Err(errors.join("\n")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::task::Priority;
+
+    fn sample_groups() -> Vec<TaskGroup> {
+        vec![
+            TaskGroup {
+                name: "Work".into(),
+                tasks: vec![
+                    Task {
+                        description: "Write the report, then send it".into(),
+                        created_at: NaiveDate::from_ymd_opt(2021, 2, 20).and_then(start_of_day),
+                        due_date: NaiveDate::from_ymd_opt(2021, 3, 4),
+                        priority: Some(Priority::High),
+                        tags: vec!["writing".into(), "email".into()],
+                        ..Default::default()
+                    },
+                    Task {
+                        description: "Tag the release".into(),
+                        is_done: true,
+                        ..Default::default()
+                    },
+                ],
+            },
+            TaskGroup {
+                name: "Chores".into(),
+                tasks: vec![Task {
+                    description: "Water the plants".into(),
+                    recurrence: Some(Recurrence::EveryNDays(3)),
+                    ..Default::default()
+                }],
+            },
+        ]
+    }
+
+    #[test]
+    fn exporting() {
+        let mut groups = sample_groups();
+        groups[0].tasks[0].created_at = None;
+        assert_eq!(
+            export(&groups, &ConversionOptions::default()),
+            Ok("group,index,description,done,created,completed,modified,due,recurrence,priority,tags\n\
+                Work,1,\"Write the report, then send it\",false,,,,2021-03-04,,high,writing email\n\
+                Work,2,Tag the release,true,,,,,,,\n\
+                Chores,1,Water the plants,false,,,,,every:3d,,\n"
+                .into())
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut options = ConversionOptions::default();
+        assert_eq!(
+            import(&export(&sample_groups(), &options).unwrap(), &options),
+            Ok(sample_groups())
+        );
+
+        options.set_delimiter("tab").unwrap();
+        options.map_column("description=Task").unwrap();
+        assert_eq!(
+            import(&export(&sample_groups(), &options).unwrap(), &options),
+            Ok(sample_groups())
+        );
+    }
+
+    #[test]
+    fn column_mapping() {
+        let mut options = ConversionOptions::default();
+        options.map_column("description=Summary").unwrap();
+        options.map_column("group=List").unwrap();
+
+        let groups = import(
+            "Status;summary;LIST;Labels\nyes;Buy milk;Errands;+food,dairy\n;Call mom;;",
+            &ConversionOptions {
+                delimiter: b';',
+                ..options.clone()
+            },
+        )
+        .unwrap();
+        assert_eq!(groups[0].name, "Errands");
+        assert_eq!(groups[0].tasks[0].description, "Buy milk");
+        // `Status` and `Labels` aren't known headers, so they're ignored
+        assert!(!groups[0].tasks[0].is_done);
+        assert!(groups[0].tasks[0].tags.is_empty());
+        assert_eq!(groups[1].name, DEFAULT_GROUP);
+
+        options.map_column("done=Status").unwrap();
+        options.map_column("tags=Labels").unwrap();
+        let groups = import(
+            "Status,Summary,List,Labels\nyes,Buy milk,Errands,\"+food, dairy\"",
+            &options,
+        )
+        .unwrap();
+        assert!(groups[0].tasks[0].is_done);
+        assert_eq!(
+            groups[0].tasks[0].tags,
+            vec!["food".to_string(), "dairy".to_string()]
+        );
+
+        assert_eq!(
+            import("Status,Title\nyes,Buy milk", &options),
+            Err("there's no `Summary` column for `description`".into())
+        );
+        assert_eq!(
+            import("group,title\nWork,Ship it", &ConversionOptions::default()),
+            Err("there's no `description` column".into())
+        );
+    }
+
+    #[test]
+    fn row_errors() {
+        let contents = "description,due,priority,done\n\
+                        Ship it,2021-03-04,high,no\n\
+                        Write the docs,someday,,\n\
+                        Fix the bug,,urgent,\n";
+        assert_eq!(
+            import(contents, &ConversionOptions::default()),
+            Err(
+                "line 3: column `due`: `someday` is not a valid date. Expected YYYY-MM-DD\n\
+                 line 4: column `priority`: `urgent` is not a valid priority. Expected `low`, `medium` or `high`"
+                    .into()
+            )
+        );
+    }
+}
@@ -0,0 +1,623 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday};
+use uuid::Uuid;
+
+use super::{start_of_day, DEFAULT_GROUP};
+use crate::recurrence::Recurrence;
+use crate::task::{Priority, Task, TaskGroup};
+
+const PRODUCT_ID: &str = "-//dodo//dodo//EN";
+
+/// Recurrences that can't be written as an RRULE, such as `after:3d`, are kept in this property
+const RECURRENCE_PROPERTY: &str = "X-DODO-RECURRENCE";
+
+/// Lines longer than this many bytes must be folded
+const MAX_LINE_LEN: usize = 75;
+
+const WEEKDAYS: &[(Weekday, &str)] = &[
+    (Weekday::Mon, "MO"),
+    (Weekday::Tue, "TU"),
+    (Weekday::Wed, "WE"),
+    (Weekday::Thu, "TH"),
+    (Weekday::Fri, "FR"),
+    (Weekday::Sat, "SA"),
+    (Weekday::Sun, "SU"),
+];
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Splits a list of text values on the commas that aren't escaped, unescaping each value
+fn split_unescaped(list: &str) -> Vec<String> {
+    let mut values = vec![String::new()];
+    let mut chars = list.chars();
+
+    while let Some(ch) = chars.next() {
+        let value = values.last_mut().unwrap();
+        match ch {
+            '\\' => match chars.next() {
+                Some('n') | Some('N') => value.push('\n'),
+                Some(escaped) => value.push(escaped),
+                None => {}
+            },
+            ',' => values.push(String::new()),
+            ch => value.push(ch),
+        }
+    }
+
+    values
+}
+
+fn unescape(text: &str) -> String {
+    // Unescaped commas aren't allowed in single values, but some apps write them anyway
+    split_unescaped(text).join(",")
+}
+
+/// Splits a line into lines of at most 75 bytes, each continuation starting with a space
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut line_len = 0;
+
+    for ch in line.chars() {
+        if line_len + ch.len_utf8() > MAX_LINE_LEN {
+            folded.push_str("\r\n ");
+            line_len = 1;
+        }
+        folded.push(ch);
+        line_len += ch.len_utf8();
+    }
+
+    folded.push_str("\r\n");
+    folded
+}
+
+fn format_time(time: DateTime<Local>) -> String {
+    time.with_timezone(&Utc)
+        .format("%Y%m%dT%H%M%SZ")
+        .to_string()
+}
+
+fn priority_level(priority: Priority) -> u8 {
+    match priority {
+        Priority::High => 1,
+        Priority::Medium => 5,
+        Priority::Low => 9,
+    }
+}
+
+/// Writes a recurrence as an RRULE, if it can be written as one
+fn recurrence_rule(recurrence: &Recurrence) -> Option<String> {
+    let rule = match recurrence {
+        Recurrence::Daily => "FREQ=DAILY".into(),
+        Recurrence::Weekly(weekdays) if weekdays.is_empty() => "FREQ=WEEKLY".into(),
+        Recurrence::Weekly(weekdays) => {
+            let days: Vec<&str> = weekdays
+                .iter()
+                .filter_map(|day| WEEKDAYS.iter().find(|(weekday, _)| weekday == day))
+                .map(|(_, name)| *name)
+                .collect();
+            format!("FREQ=WEEKLY;BYDAY={}", days.join(","))
+        }
+        Recurrence::Monthly => "FREQ=MONTHLY".into(),
+        Recurrence::EveryNDays(days) => format!("FREQ=DAILY;INTERVAL={}", days),
+        Recurrence::AfterCompletion(_) => return None,
+    };
+    Some(rule)
+}
+
+/// Writes a task as a VTODO entry. `stamp` is when the entry is being written
+fn write_task(
+    contents: &mut String,
+    group: &TaskGroup,
+    task: &Task,
+    due_date: NaiveDate,
+    stamp: DateTime<Utc>,
+) {
+    let uid = task
+        .uid
+        .clone()
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    let mut lines = vec![
+        "BEGIN:VTODO".to_string(),
+        format!("UID:{}", escape(&uid)),
+        format!("DTSTAMP:{}", stamp.format("%Y%m%dT%H%M%SZ")),
+    ];
+
+    lines.extend(
+        task.created_at
+            .map(|time| format!("CREATED:{}", format_time(time))),
+    );
+    lines.extend(
+        task.modified_at
+            .map(|time| format!("LAST-MODIFIED:{}", format_time(time))),
+    );
+    lines.push(format!("SUMMARY:{}", escape(&task.description)));
+    lines.push(format!("DUE;VALUE=DATE:{}", due_date.format("%Y%m%d")));
+    if task.is_done {
+        lines.push("STATUS:COMPLETED".into());
+        lines.extend(
+            task.completed_at
+                .map(|time| format!("COMPLETED:{}", format_time(time))),
+        );
+    } else {
+        lines.push("STATUS:NEEDS-ACTION".into());
+    }
+    lines.extend(
+        task.priority
+            .map(|priority| format!("PRIORITY:{}", priority_level(priority))),
+    );
+
+    // The group comes first, followed by the task's tags
+    let categories: Vec<String> = std::iter::once(&group.name)
+        .chain(&task.tags)
+        .map(|category| escape(category))
+        .collect();
+    lines.push(format!("CATEGORIES:{}", categories.join(",")));
+
+    if let Some(recurrence) = &task.recurrence {
+        match recurrence_rule(recurrence) {
+            Some(rule) => lines.push(format!("RRULE:{}", rule)),
+            None => lines.push(format!("{}:{}", RECURRENCE_PROPERTY, recurrence.token())),
+        }
+    }
+    lines.push("END:VTODO".into());
+
+    for line in lines {
+        contents.push_str(&fold(&line));
+    }
+}
+
+/// Writes every task that has a due date as a VTODO entry of a calendar.
+/// Tasks keep their UID, so calendar apps update the entries they already have when the list
+/// is exported again. `stamp` is when the calendar is being written
+pub fn export(task_groups: &[TaskGroup], stamp: DateTime<Utc>) -> String {
+    let mut contents = String::new();
+    contents.push_str(&fold("BEGIN:VCALENDAR"));
+    contents.push_str(&fold("VERSION:2.0"));
+    contents.push_str(&fold(&format!("PRODID:{}", PRODUCT_ID)));
+
+    for group in task_groups {
+        for task in &group.tasks {
+            if let Some(due_date) = task.due_date {
+                write_task(&mut contents, group, task, due_date, stamp);
+            }
+        }
+    }
+
+    contents.push_str(&fold("END:VCALENDAR"));
+    contents
+}
+
+/// Joins folded lines back together, returning each line along with the number of the line it starts on
+fn unfold(contents: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = vec![];
+
+    for (line_idx, line) in contents.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        match lines.last_mut() {
+            Some((_, previous)) if line.starts_with(' ') || line.starts_with('\t') => {
+                previous.push_str(&line[1..])
+            }
+            _ if line.is_empty() => {}
+            _ => lines.push((line_idx + 1, line.to_string())),
+        }
+    }
+
+    lines
+}
+
+/// Splits a content line into its name and its value, e.g. `DUE;VALUE=DATE:20210304`
+/// into (`DUE`, `20210304`). Parameters are dropped, as values are told apart by their syntax
+fn parse_content_line(line: &str) -> Option<(String, &str)> {
+    // Parameter values may contain colons when they're quoted
+    let mut is_quoted = false;
+    let colon = line.char_indices().find_map(|(idx, ch)| {
+        match ch {
+            '"' => is_quoted = !is_quoted,
+            ':' if !is_quoted => return Some(idx),
+            _ => {}
+        }
+        None
+    })?;
+
+    let name = line[..colon].split(';').next()?.to_uppercase();
+    Some((name, &line[colon + 1..]))
+}
+
+/// Parses DATE-TIME values, e.g. `20210304T120000Z`. Times without a timezone are taken as local,
+/// and so are the ones with a TZID, as timezone definitions aren't read.
+/// DATE values, e.g. `20210304`, are taken as the start of the day
+fn parse_time(value: &str) -> Result<DateTime<Local>, String> {
+    let invalid = || format!("`{}` is not a valid date-time", value);
+
+    if let Some(utc) = value.strip_suffix('Z') {
+        return NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")
+            .map(|time| Utc.from_utc_datetime(&time).with_timezone(&Local))
+            .map_err(|_| invalid());
+    }
+    if let Ok(time) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+        return Local
+            .from_local_datetime(&time)
+            .earliest()
+            .ok_or_else(invalid);
+    }
+
+    parse_date(value)
+        .ok()
+        .and_then(start_of_day)
+        .ok_or_else(invalid)
+}
+
+/// Parses DATE values, or the date of DATE-TIME values
+fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    value
+        .get(..8)
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y%m%d").ok())
+        .ok_or_else(|| format!("`{}` is not a valid date", value))
+}
+
+fn parse_priority(value: &str) -> Result<Option<Priority>, String> {
+    match value.parse::<u8>() {
+        Ok(0) => Ok(None),
+        Ok(1..=4) => Ok(Some(Priority::High)),
+        Ok(5) => Ok(Some(Priority::Medium)),
+        Ok(6..=9) => Ok(Some(Priority::Low)),
+        _ => Err(format!(
+            "`{}` is not a valid priority. Expected a number from 0 to 9",
+            value
+        )),
+    }
+}
+
+/// Reads the RRULEs that have an equivalent recurrence. Returns None for the others
+fn parse_recurrence_rule(rule: &str) -> Option<Recurrence> {
+    let mut frequency = None;
+    let mut interval = 1;
+    let mut weekdays = vec![];
+
+    for part in rule.split(';') {
+        let (key, value) = part.split_once('=')?;
+        match key.to_uppercase().as_str() {
+            "FREQ" => frequency = Some(value.to_uppercase()),
+            "INTERVAL" => interval = value.parse().ok().filter(|&days| days > 0)?,
+            "BYDAY" => {
+                for day in value.split(',') {
+                    let (weekday, _) = WEEKDAYS
+                        .iter()
+                        .find(|(_, name)| name.eq_ignore_ascii_case(day))?;
+                    weekdays.push(*weekday);
+                }
+            }
+            // The rule is only taken as is if it doesn't end or have any other restriction
+            "WKST" => {}
+            _ => return None,
+        }
+    }
+
+    match (frequency?.as_str(), interval) {
+        ("DAILY", 1) if weekdays.is_empty() => Some(Recurrence::Daily),
+        ("DAILY", days) if weekdays.is_empty() => Some(Recurrence::EveryNDays(days)),
+        ("WEEKLY", 1) => Some(Recurrence::Weekly(weekdays)),
+        ("MONTHLY", 1) if weekdays.is_empty() => Some(Recurrence::Monthly),
+        _ => None,
+    }
+}
+
+/// The task being read from a VTODO entry, along with its group
+struct Entry {
+    start_line: usize,
+    group: String,
+    task: Task,
+}
+
+/// Applies a property of a VTODO entry to the task being read
+fn apply_property(entry: &mut Entry, name: &str, value: &str) -> Result<(), String> {
+    let task = &mut entry.task;
+
+    match name {
+        "UID" => task.uid = Some(unescape(value)),
+        "SUMMARY" => task.description = unescape(value),
+        "STATUS" => task.is_done = value.eq_ignore_ascii_case("COMPLETED"),
+        "COMPLETED" => {
+            task.is_done = true;
+            task.completed_at = Some(parse_time(value)?);
+        }
+        "CREATED" => task.created_at = Some(parse_time(value)?),
+        "LAST-MODIFIED" => task.modified_at = Some(parse_time(value)?),
+        "DUE" => task.due_date = Some(parse_date(value)?),
+        "PRIORITY" => task.priority = parse_priority(value)?,
+        "CATEGORIES" => {
+            let mut categories = split_unescaped(value)
+                .into_iter()
+                .map(|category| category.trim().to_string())
+                .filter(|category| !category.is_empty());
+            // Entries may have several CATEGORIES properties: only the first category is the group
+            if entry.group.is_empty() {
+                entry.group = categories.next().unwrap_or_default();
+            }
+            for tag in categories {
+                let tag = tag.replace(' ', "_");
+                if !task.tags.contains(&tag) {
+                    task.tags.push(tag);
+                }
+            }
+        }
+        "RRULE" if task.recurrence.is_none() => {
+            task.recurrence = parse_recurrence_rule(value);
+        }
+        RECURRENCE_PROPERTY => {
+            task.recurrence = Some(
+                Recurrence::from_token(value)
+                    .unwrap_or_else(|| Err(format!("`{}` is not a valid recurrence", value)))?,
+            );
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Reads the VTODO entries of a calendar, grouping tasks by their first category.
+/// Other components, such as events, are ignored, as are recurrences that have no equivalent.
+/// Every invalid property is reported, each on its own line.
+pub fn import(contents: &str) -> Result<Vec<TaskGroup>, String> {
+    let mut task_groups: Vec<TaskGroup> = vec![];
+    let mut errors = vec![];
+    // The components the current line is part of, innermost last
+    let mut components: Vec<String> = vec![];
+    let mut entry: Option<Entry> = None;
+
+    for (line_no, line) in unfold(contents) {
+        let (name, value) = match parse_content_line(&line) {
+            Some(parsed) => parsed,
+            None => {
+                errors.push(format!("line {}: expected `NAME:VALUE`", line_no));
+                continue;
+            }
+        };
+
+        match name.as_str() {
+            "BEGIN" => {
+                let component = value.to_uppercase();
+                if component == "VTODO"
+                    && components.last().map(String::as_str) == Some("VCALENDAR")
+                {
+                    entry = Some(Entry {
+                        start_line: line_no,
+                        group: String::new(),
+                        task: Task::default(),
+                    });
+                }
+                components.push(component);
+            }
+            "END" => {
+                if components.pop().as_deref() != Some(value.to_uppercase().as_str()) {
+                    errors.push(format!("line {}: unexpected `END:{}`", line_no, value));
+                    break;
+                }
+                // Only the entries that are right inside the calendar were read
+                let is_entry = components.last().map(String::as_str) == Some("VCALENDAR");
+                if value.eq_ignore_ascii_case("VTODO") && is_entry {
+                    if let Some(Entry { group, task, .. }) = entry.take() {
+                        let group_name = match group.is_empty() {
+                            true => DEFAULT_GROUP.to_string(),
+                            false => group,
+                        };
+                        match task_groups
+                            .iter_mut()
+                            .find(|group| group.name == group_name)
+                        {
+                            Some(group) => group.tasks.push(task),
+                            None => task_groups.push(TaskGroup {
+                                name: group_name,
+                                tasks: vec![task],
+                            }),
+                        }
+                    }
+                }
+            }
+            name => {
+                if let (Some(entry), Some("VTODO")) =
+                    (entry.as_mut(), components.last().map(String::as_str))
+                {
+                    if let Err(err) = apply_property(entry, name, value) {
+                        errors.push(format!("line {}: {}: {}", line_no, name, err));
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(entry) = entry {
+        errors.push(format!(
+            "line {}: the VTODO entry is never closed",
+            entry.start_line
+        ));
+    }
+
+    match errors.is_empty() {
+        true => Ok(task_groups),
+        false => Err(errors.join("\n")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn utc(year: i32, month: u32, day: u32, hour: u32) -> DateTime<Local> {
+        Utc.with_ymd_and_hms(year, month, day, hour, 0, 0)
+            .unwrap()
+            .with_timezone(&Local)
+    }
+
+    fn sample_groups() -> Vec<TaskGroup> {
+        vec![
+            TaskGroup {
+                name: "Work, mostly".into(),
+                tasks: vec![
+                    Task {
+                        description: "Write the report; then send it".into(),
+                        created_at: Some(utc(2021, 2, 20, 9)),
+                        modified_at: Some(utc(2021, 2, 21, 9)),
+                        due_date: Some(date(2021, 3, 4)),
+                        priority: Some(Priority::High),
+                        tags: vec!["writing".into()],
+                        uid: Some("report".into()),
+                        ..Default::default()
+                    },
+                    Task {
+                        description: "Tag the release".into(),
+                        is_done: true,
+                        completed_at: Some(utc(2021, 3, 2, 18)),
+                        due_date: Some(date(2021, 3, 3)),
+                        recurrence: Some(Recurrence::AfterCompletion(30)),
+                        uid: Some("release".into()),
+                        ..Default::default()
+                    },
+                    Task {
+                        description: "Tasks without a due date aren't exported".into(),
+                        ..Default::default()
+                    },
+                ],
+            },
+            TaskGroup {
+                name: "Chores".into(),
+                tasks: vec![Task {
+                    description: "Water the plants".into(),
+                    due_date: Some(date(2021, 3, 1)),
+                    recurrence: Some(Recurrence::Weekly(vec![Weekday::Mon, Weekday::Thu])),
+                    uid: Some("plants".into()),
+                    ..Default::default()
+                }],
+            },
+        ]
+    }
+
+    #[test]
+    fn exporting() {
+        let stamp = Utc.with_ymd_and_hms(2021, 3, 1, 12, 0, 0).unwrap();
+        let expected = [
+            "BEGIN:VCALENDAR",
+            "VERSION:2.0",
+            "PRODID:-//dodo//dodo//EN",
+            "BEGIN:VTODO",
+            "UID:report",
+            "DTSTAMP:20210301T120000Z",
+            "CREATED:20210220T090000Z",
+            "LAST-MODIFIED:20210221T090000Z",
+            "SUMMARY:Write the report\\; then send it",
+            "DUE;VALUE=DATE:20210304",
+            "STATUS:NEEDS-ACTION",
+            "PRIORITY:1",
+            "CATEGORIES:Work\\, mostly,writing",
+            "END:VTODO",
+            "BEGIN:VTODO",
+            "UID:release",
+            "DTSTAMP:20210301T120000Z",
+            "SUMMARY:Tag the release",
+            "DUE;VALUE=DATE:20210303",
+            "STATUS:COMPLETED",
+            "COMPLETED:20210302T180000Z",
+            "CATEGORIES:Work\\, mostly",
+            "X-DODO-RECURRENCE:after:30d",
+            "END:VTODO",
+            "BEGIN:VTODO",
+            "UID:plants",
+            "DTSTAMP:20210301T120000Z",
+            "SUMMARY:Water the plants",
+            "DUE;VALUE=DATE:20210301",
+            "STATUS:NEEDS-ACTION",
+            "CATEGORIES:Chores",
+            "RRULE:FREQ=WEEKLY;BYDAY=MO,TH",
+            "END:VTODO",
+            "END:VCALENDAR",
+            "",
+        ];
+        assert_eq!(export(&sample_groups(), stamp), expected.join("\r\n"));
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut groups = sample_groups();
+        groups[0].tasks.pop();
+
+        let exported = export(&sample_groups(), Utc::now());
+        assert_eq!(import(&exported), Ok(groups));
+    }
+
+    #[test]
+    fn folding() {
+        let description = "A very long description, ".repeat(5);
+        let line = format!("SUMMARY:{}", escape(&description));
+        let folded = fold(&line);
+
+        assert!(folded
+            .split("\r\n")
+            .all(|folded_line| folded_line.len() <= MAX_LINE_LEN));
+        let unfolded = unfold(&folded);
+        assert_eq!(unfolded, vec![(1, line)]);
+    }
+
+    #[test]
+    fn importing() {
+        let contents = "BEGIN:VCALENDAR\n\
+                        PRODID:-//Another app//EN\n\
+                        BEGIN:VEVENT\n\
+                        SUMMARY:Events are ignored\n\
+                        END:VEVENT\n\
+                        BEGIN:VTODO\n\
+                        UID:1234@example.com\n\
+                        SUMMARY;LANGUAGE=en:Renew the\n \x20passport\n\
+                        DUE;TZID=\"Europe/Lisbon:Summer\":20210304T170000\n\
+                        PRIORITY:3\n\
+                        CATEGORIES:Errands,Paperwork\n\
+                        RRULE:FREQ=YEARLY\n\
+                        BEGIN:VALARM\n\
+                        SUMMARY:Alarms are ignored too\n\
+                        END:VALARM\n\
+                        END:VTODO\n\
+                        BEGIN:VTODO\n\
+                        SUMMARY:Call mom\n\
+                        STATUS:COMPLETED\n\
+                        RRULE:FREQ=DAILY;INTERVAL=2\n\
+                        END:VTODO\n\
+                        END:VCALENDAR\n";
+        let groups = import(contents).unwrap();
+
+        assert_eq!(groups[0].name, "Errands");
+        let task = &groups[0].tasks[0];
+        assert_eq!(task.description, "Renew the passport");
+        assert_eq!(task.uid.as_deref(), Some("1234@example.com"));
+        assert_eq!(task.due_date, Some(date(2021, 3, 4)));
+        assert_eq!(task.priority, Some(Priority::High));
+        assert_eq!(task.tags, vec!["Paperwork".to_string()]);
+        assert_eq!(task.recurrence, None);
+
+        assert_eq!(groups[1].name, DEFAULT_GROUP);
+        assert!(groups[1].tasks[0].is_done);
+        assert_eq!(
+            groups[1].tasks[0].recurrence,
+            Some(Recurrence::EveryNDays(2))
+        );
+
+        assert_eq!(
+            import(
+                "BEGIN:VCALENDAR\nBEGIN:VTODO\nDUE:someday\nPRIORITY:high\nBEGIN:VTODO\n\
+                 END:VTODO\nEND:VCALENDAR"
+            ),
+            Err("line 3: DUE: `someday` is not a valid date\n\
+                 line 4: PRIORITY: `high` is not a valid priority. Expected a number from 0 to 9\n\
+                 line 7: unexpected `END:VCALENDAR`\n\
+                 line 2: the VTODO entry is never closed"
+                .into())
+        );
+    }
+}
@@ -0,0 +1,43 @@
+use crate::task::TaskGroup;
+
+/// Writes the groups as a JSON array, with every field of their tasks, timestamps included
+pub fn export(task_groups: &[TaskGroup]) -> Result<String, String> {
+    serde_json::to_string_pretty(task_groups)
+        .map(|contents| contents + "\n")
+        .map_err(|err| err.to_string())
+}
+
+/// Reads groups written by `export`
+pub fn import(contents: &str) -> Result<Vec<TaskGroup>, String> {
+    serde_json::from_str(contents).map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeZone};
+
+    use super::*;
+    use crate::task::Task;
+
+    #[test]
+    fn round_trip() {
+        let time = Local.with_ymd_and_hms(2021, 3, 2, 18, 30, 0).unwrap();
+        let groups = vec![TaskGroup {
+            name: "Work".into(),
+            tasks: vec![Task {
+                description: "Tag the release".into(),
+                is_done: true,
+                created_at: Some(time),
+                completed_at: Some(time),
+                modified_at: Some(time),
+                tags: vec!["release".into()],
+                ..Default::default()
+            }],
+        }];
+
+        let exported = export(&groups).unwrap();
+        assert!(exported.contains("\"modified_at\": \"2021-03-02T18:30:00"));
+        assert_eq!(import(&exported), Ok(groups));
+        assert!(import("[{\"name\": \"Work\"}]").is_err());
+    }
+}
@@ -0,0 +1,315 @@
+use super::{DEFAULT_GROUP, PATH_SEPARATOR};
+use crate::task::{Task, TaskGroup};
+
+/// How much nested items are indented for each level
+const INDENT: &str = "  ";
+
+/// The name of the group holding the items nested in the given task
+fn nested_group_name(group: &TaskGroup, task: &Task) -> String {
+    format!("{}{}{}", group.name, PATH_SEPARATOR, task.description)
+}
+
+/// Writes the tasks of a group as checklist items at the given depth, each followed by
+/// the items nested in it. `is_nested` tells which groups are nested, and `is_written` which
+/// of them were already written, in case several tasks have the same description
+fn write_items(
+    section: &mut String,
+    task_groups: &[TaskGroup],
+    group_idx: usize,
+    depth: usize,
+    is_nested: &[bool],
+    is_written: &mut [bool],
+) {
+    let group = &task_groups[group_idx];
+    for task in &group.tasks {
+        let checkbox = if task.is_done { "x" } else { " " };
+        section.push_str(&format!(
+            "{}- [{}] {}\n",
+            INDENT.repeat(depth),
+            checkbox,
+            task.to_input()
+        ));
+
+        let name = nested_group_name(group, task);
+        let nested_idx = (0..task_groups.len())
+            .find(|&idx| is_nested[idx] && !is_written[idx] && task_groups[idx].name == name);
+        if let Some(nested_idx) = nested_idx {
+            is_written[nested_idx] = true;
+            write_items(
+                section,
+                task_groups,
+                nested_idx,
+                depth + 1,
+                is_nested,
+                is_written,
+            );
+        }
+    }
+}
+
+/// Writes the groups as Markdown checklists, e.g.
+///
+///     ## Work
+///
+///     - [ ] Write the report +writing due:2021-03-04
+///       - [x] Gather the numbers
+///     - [x] Tag the release
+///
+/// Groups named after a task of another group, such as `Work/Write the report`,
+/// are written as items nested in that task
+pub fn export(task_groups: &[TaskGroup]) -> String {
+    // Empty groups are written as headings, so that they aren't lost
+    let is_nested: Vec<bool> = task_groups
+        .iter()
+        .map(|nested| {
+            !nested.tasks.is_empty()
+                && task_groups.iter().any(|group| {
+                    group
+                        .tasks
+                        .iter()
+                        .any(|task| nested_group_name(group, task) == nested.name)
+                })
+        })
+        .collect();
+    let mut is_written = vec![false; task_groups.len()];
+
+    let mut sections = vec![];
+    for (group_idx, group) in task_groups.iter().enumerate() {
+        if is_nested[group_idx] {
+            continue;
+        }
+        let mut section = format!("## {}\n", group.name);
+        if !group.tasks.is_empty() {
+            section.push('\n');
+        }
+        write_items(
+            &mut section,
+            task_groups,
+            group_idx,
+            0,
+            &is_nested,
+            &mut is_written,
+        );
+        sections.push(section);
+    }
+
+    sections.join("\n")
+}
+
+/// If the line is a checklist item such as `- [x] Buy milk`, returns whether it's checked
+/// along with its text. Items may be indented, and use `-`, `*` or `+` as bullets.
+fn parse_item(line: &str) -> Option<(bool, &str)> {
+    let item = line
+        .trim_start()
+        .strip_prefix(|ch| ch == '-' || ch == '*' || ch == '+')?
+        .strip_prefix(' ')?
+        .trim_start();
+
+    let (is_done, text) = match item.get(..3)? {
+        "[ ]" => (false, &item[3..]),
+        "[x]" | "[X]" => (true, &item[3..]),
+        _ => return None,
+    };
+    Some((is_done, text.trim()))
+}
+
+/// Reads the checklists of a Markdown file. `## Group` headings (or deeper ones) start a group,
+/// while the document's `# Title` is ignored. As tasks can't have subtasks, items nested in
+/// another item go to a group named after it, e.g. `Work/Write the report`.
+/// Anything else, such as paragraphs, is ignored.
+pub fn import(contents: &str) -> Result<Vec<TaskGroup>, String> {
+    let mut task_groups: Vec<TaskGroup> = vec![];
+    // The group of the current section, if there's one yet
+    let mut section_idx = None;
+    // The indentation and description of the items the current item is nested in
+    let mut parents: Vec<(usize, String)> = vec![];
+
+    for (line_idx, line) in contents.lines().enumerate() {
+        let heading_level = line.chars().take_while(|&ch| ch == '#').count();
+        if heading_level >= 2 && line[heading_level..].starts_with(' ') {
+            task_groups.push(TaskGroup {
+                name: line[heading_level..].trim().into(),
+                tasks: vec![],
+            });
+            section_idx = Some(task_groups.len() - 1);
+            parents.clear();
+            continue;
+        }
+
+        let (is_done, text) = match parse_item(line) {
+            Some(item) => item,
+            None => continue,
+        };
+
+        let mut task =
+            Task::from_input(text).map_err(|err| format!("line {}: {}", line_idx + 1, err))?;
+        if is_done {
+            task.flip();
+        }
+
+        let section_idx = *section_idx.get_or_insert_with(|| {
+            task_groups.push(TaskGroup {
+                name: DEFAULT_GROUP.into(),
+                tasks: vec![],
+            });
+            task_groups.len() - 1
+        });
+
+        let indent = line.len() - line.trim_start().len();
+        parents.retain(|(parent_indent, _)| *parent_indent < indent);
+        let group_idx = match parents.is_empty() {
+            true => section_idx,
+            false => {
+                let mut names = vec![task_groups[section_idx].name.as_str()];
+                names.extend(parents.iter().map(|(_, description)| description.as_str()));
+                let name = names.join(&PATH_SEPARATOR.to_string());
+                match task_groups.iter().position(|group| group.name == name) {
+                    Some(group_idx) => group_idx,
+                    None => {
+                        task_groups.push(TaskGroup {
+                            name,
+                            tasks: vec![],
+                        });
+                        task_groups.len() - 1
+                    }
+                }
+            }
+        };
+
+        parents.push((indent, task.description.clone()));
+        task_groups[group_idx].tasks.push(task);
+    }
+
+    Ok(task_groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::task::Priority;
+
+    /// Timestamps depend on when the test runs, so they're cleared before comparisons
+    fn clear_timestamps(task_groups: &mut [TaskGroup]) {
+        for task in task_groups
+            .iter_mut()
+            .flat_map(|group| group.tasks.iter_mut())
+        {
+            task.created_at = None;
+            task.completed_at = None;
+            task.modified_at = None;
+        }
+    }
+
+    fn sample_groups() -> Vec<TaskGroup> {
+        vec![
+            TaskGroup {
+                name: "Work".into(),
+                tasks: vec![
+                    Task {
+                        description: "Write the report".into(),
+                        due_date: NaiveDate::from_ymd_opt(2021, 3, 4),
+                        priority: Some(Priority::High),
+                        tags: vec!["writing".into()],
+                        ..Default::default()
+                    },
+                    Task {
+                        description: "Tag the release".into(),
+                        is_done: true,
+                        ..Default::default()
+                    },
+                ],
+            },
+            TaskGroup {
+                name: "Work/Write the report".into(),
+                tasks: vec![Task {
+                    description: "Gather the numbers".into(),
+                    is_done: true,
+                    ..Default::default()
+                }],
+            },
+            TaskGroup {
+                name: "Empty group".into(),
+                tasks: vec![],
+            },
+        ]
+    }
+
+    #[test]
+    fn exporting() {
+        assert_eq!(
+            export(&sample_groups()),
+            "## Work\n\n\
+             - [ ] Write the report +writing due:2021-03-04 priority:high\n\
+             \x20 - [x] Gather the numbers\n\
+             - [x] Tag the release\n\
+             \n\
+             ## Empty group\n"
+        );
+
+        // Nested groups are nested no matter where they are in the list
+        let mut reordered = sample_groups();
+        reordered.rotate_left(1);
+        let exported = export(&reordered);
+        assert!(exported.contains("\n  - [x] Gather the numbers\n"));
+        assert!(!exported.contains("## Work/"));
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut imported = import(&export(&sample_groups())).unwrap();
+        clear_timestamps(&mut imported);
+        assert_eq!(imported, sample_groups());
+    }
+
+    #[test]
+    fn importing() {
+        let contents = "# Release checklist\n\
+                        - [ ] Bump the version\n\
+                        Some notes that are ignored.\n\
+                        ### Docs\n\
+                        * [X] Update the README\n\
+                        \x20 - [ ] Add screenshots\n\
+                        \x20   - [ ] Crop them\n\
+                        \x20 - [ ] Fix the links\n\
+                        - a plain item\n\
+                        - [ ] Update the changelog\n";
+        let groups = import(contents).unwrap();
+
+        let summary: Vec<(&str, Vec<(&str, bool)>)> = groups
+            .iter()
+            .map(|group| {
+                let tasks = group
+                    .tasks
+                    .iter()
+                    .map(|task| (task.description.as_str(), task.is_done))
+                    .collect();
+                (group.name.as_str(), tasks)
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (DEFAULT_GROUP, vec![("Bump the version", false)]),
+                (
+                    "Docs",
+                    vec![("Update the README", true), ("Update the changelog", false)]
+                ),
+                (
+                    "Docs/Update the README",
+                    vec![("Add screenshots", false), ("Fix the links", false)]
+                ),
+                (
+                    "Docs/Update the README/Add screenshots",
+                    vec![("Crop them", false)]
+                ),
+            ]
+        );
+
+        assert_eq!(
+            import("## Work\n- [ ] Ship it due:someday"),
+            Err("line 2: `someday` is not a valid date. Expected YYYY-MM-DD".into())
+        );
+    }
+}
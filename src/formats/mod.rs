@@ -0,0 +1,227 @@
+use std::{collections::BTreeMap, fmt, path::Path, str::FromStr};
+
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+
+use crate::task::TaskGroup;
+
+mod csv;
+mod ical;
+mod json;
+mod markdown;
+mod org;
+mod todotxt;
+
+/// The names of every format, as given to `--format`
+pub const FORMAT_NAMES: &[&str] = &["todotxt", "markdown", "csv", "ical", "org", "json"];
+
+/// The group of imported tasks that aren't part of any group
+const DEFAULT_GROUP: &str = "Inbox";
+
+/// Separates the names of nested groups, e.g. `Work/Backend`
+const PATH_SEPARATOR: char = '/';
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+/// The formats todo lists can be imported from and exported to
+pub enum Format {
+    /// See http://todotxt.org
+    TodoTxt,
+    /// Checklists under `## Group` headings
+    Markdown,
+    /// One row per task, for spreadsheets
+    Csv,
+    /// iCalendar VTODO entries, for calendar apps
+    Ical,
+    /// `TODO` and `DONE` headlines under group headlines, for Emacs
+    Org,
+    /// The groups and every field of their tasks, for scripts and other programs
+    Json,
+}
+
+impl Format {
+    /// Guesses the format of a file from its extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "txt" => Some(Format::TodoTxt),
+            "md" | "markdown" => Some(Format::Markdown),
+            "csv" => Some(Format::Csv),
+            "ics" | "ical" => Some(Format::Ical),
+            "org" => Some(Format::Org),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_lowercase().as_str() {
+            "todotxt" => Ok(Format::TodoTxt),
+            "markdown" | "md" => Ok(Format::Markdown),
+            "csv" => Ok(Format::Csv),
+            "ical" | "ics" | "icalendar" => Ok(Format::Ical),
+            "org" | "org-mode" => Ok(Format::Org),
+            "json" => Ok(Format::Json),
+            _ => Err(format!(
+                "`{}` is not a known format. Expected one of: {}",
+                format,
+                FORMAT_NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Format::TodoTxt => write!(f, "todotxt"),
+            Format::Markdown => write!(f, "markdown"),
+            Format::Csv => write!(f, "csv"),
+            Format::Ical => write!(f, "ical"),
+            Format::Org => write!(f, "org"),
+            Format::Json => write!(f, "json"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+/// How groups are written in formats that have no notion of groups
+pub enum GroupMarker {
+    /// As a `+project`
+    Project,
+    /// As an `@context`
+    Context,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+/// Options on how to convert a list to or from another format
+pub struct ConversionOptions {
+    /// The format to convert to or from. None means it's guessed from the file's extension
+    pub format: Option<Format>,
+    /// How groups are written in todo.txt
+    pub group_marker: GroupMarker,
+    /// The CSV header used for each field, for the fields whose header isn't the field's name
+    pub columns: BTreeMap<String, String>,
+    /// The character separating CSV fields
+    pub delimiter: u8,
+}
+
+impl Default for ConversionOptions {
+    fn default() -> Self {
+        Self {
+            format: None,
+            group_marker: GroupMarker::Project,
+            columns: BTreeMap::new(),
+            delimiter: b',',
+        }
+    }
+}
+
+impl ConversionOptions {
+    /// Applies the option given by `word`, e.g. `group-as:context`, `column:description=Task`
+    /// or `delimiter:;`. Returns None if the word isn't an option at all.
+    pub fn apply(&mut self, word: &str) -> Option<Result<(), String>> {
+        let (key, value) = word.split_once(':')?;
+
+        let result = match key {
+            "format" => value.parse().map(|format| self.format = Some(format)),
+            "group-as" => self.set_group_marker(value),
+            "column" => self.map_column(value),
+            "delimiter" => self.set_delimiter(value),
+            _ => return None,
+        };
+        Some(result)
+    }
+
+    pub fn set_group_marker(&mut self, marker: &str) -> Result<(), String> {
+        self.group_marker = match marker {
+            "project" => GroupMarker::Project,
+            "context" => GroupMarker::Context,
+            marker => {
+                return Err(format!(
+                    "`{}` is not a valid group marker. Expected `project` or `context`",
+                    marker
+                ))
+            }
+        };
+        Ok(())
+    }
+
+    /// Reads a column mapping in the `field=Header` format
+    pub fn map_column(&mut self, mapping: &str) -> Result<(), String> {
+        let (field, header) = mapping
+            .split_once('=')
+            .filter(|(_, header)| !header.is_empty())
+            .ok_or_else(|| {
+                format!(
+                    "`{}` is not a valid column mapping. Expected `FIELD=HEADER`",
+                    mapping
+                )
+            })?;
+
+        if !csv::FIELDS.contains(&field) {
+            return Err(format!(
+                "`{}` is not a known field. Expected one of: {}",
+                field,
+                csv::FIELDS.join(", ")
+            ));
+        }
+        self.columns.insert(field.into(), header.into());
+        Ok(())
+    }
+
+    /// Reads a single-character delimiter, or `tab`
+    pub fn set_delimiter(&mut self, delimiter: &str) -> Result<(), String> {
+        self.delimiter = match delimiter {
+            "tab" => b'\t',
+            delimiter if delimiter.len() == 1 => delimiter.as_bytes()[0],
+            delimiter => {
+                return Err(format!(
+                    "`{}` is not a valid delimiter. Expected a single character or `tab`",
+                    delimiter
+                ))
+            }
+        };
+        Ok(())
+    }
+}
+
+/// Returns midnight of the given day, as dates are all most formats keep of timestamps
+fn start_of_day(date: NaiveDate) -> Option<DateTime<Local>> {
+    Local
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+        .earliest()
+}
+
+/// Writes the given groups in the given format
+pub fn export(
+    format: Format,
+    task_groups: &[TaskGroup],
+    options: &ConversionOptions,
+) -> Result<String, String> {
+    match format {
+        Format::TodoTxt => Ok(todotxt::export(task_groups, options)),
+        Format::Markdown => Ok(markdown::export(task_groups)),
+        Format::Csv => csv::export(task_groups, options),
+        Format::Ical => Ok(ical::export(task_groups, Utc::now())),
+        Format::Org => Ok(org::export(task_groups)),
+        Format::Json => json::export(task_groups),
+    }
+}
+
+/// Reads groups written in the given format
+pub fn import(
+    format: Format,
+    contents: &str,
+    options: &ConversionOptions,
+) -> Result<Vec<TaskGroup>, String> {
+    match format {
+        Format::TodoTxt => todotxt::import(contents, options),
+        Format::Markdown => markdown::import(contents),
+        Format::Csv => csv::import(contents, options),
+        Format::Ical => ical::import(contents),
+        Format::Org => org::import(contents),
+        Format::Json => json::import(contents),
+    }
+}
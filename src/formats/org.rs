@@ -0,0 +1,551 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+
+use super::{start_of_day, DEFAULT_GROUP, PATH_SEPARATOR};
+use crate::recurrence::Recurrence;
+use crate::task::{Priority, Task, TaskGroup};
+
+fn priority_letter(priority: Priority) -> char {
+    match priority {
+        Priority::High => 'A',
+        Priority::Medium => 'B',
+        Priority::Low => 'C',
+    }
+}
+
+fn parse_priority(letter: &str) -> Option<Priority> {
+    match letter {
+        "A" => Some(Priority::High),
+        "B" => Some(Priority::Medium),
+        "C" => Some(Priority::Low),
+        _ => None,
+    }
+}
+
+/// Writes a recurrence as the repeater of a timestamp, e.g. `+1w`, if it can be written as one
+fn repeater(recurrence: &Recurrence) -> Option<String> {
+    let repeater = match recurrence {
+        Recurrence::Daily => "+1d".into(),
+        Recurrence::Weekly(weekdays) if weekdays.is_empty() => "+1w".into(),
+        Recurrence::Weekly(_) => return None,
+        Recurrence::Monthly => "+1m".into(),
+        Recurrence::EveryNDays(days) => format!("+{}d", days),
+        Recurrence::AfterCompletion(days) => format!(".+{}d", days),
+    };
+    Some(repeater)
+}
+
+/// Reads repeaters such as `+3d`, `++1w` or `.+2d`.
+/// Returns None for the ones with no equivalent recurrence, such as `+2m`
+fn parse_repeater(word: &str) -> Option<Recurrence> {
+    let (after_completion, interval) = match word.strip_prefix(".+") {
+        Some(interval) => (true, interval),
+        None => (false, word.trim_start_matches('+')),
+    };
+    let (unit_idx, unit) = interval.char_indices().last()?;
+    let count: u32 = interval[..unit_idx]
+        .parse()
+        .ok()
+        .filter(|&count| count > 0)?;
+
+    let days = match unit {
+        'd' => count,
+        'w' => count.checked_mul(7)?,
+        'm' if count == 1 && !after_completion => return Some(Recurrence::Monthly),
+        _ => return None,
+    };
+    match (after_completion, days) {
+        (true, days) => Some(Recurrence::AfterCompletion(days)),
+        (false, 1) => Some(Recurrence::Daily),
+        (false, 7) => Some(Recurrence::Weekly(vec![])),
+        (false, days) => Some(Recurrence::EveryNDays(days)),
+    }
+}
+
+fn inactive_timestamp(time: DateTime<Local>) -> String {
+    time.format("[%Y-%m-%d %a %H:%M]").to_string()
+}
+
+/// Writes a task as a headline of the given level, e.g.
+///
+///     ** TODO [#A] Write the report :writing:
+///     DEADLINE: <2021-03-04 Thu +1w>
+///     :PROPERTIES:
+///     :CREATED:  [2021-02-20 Sat 09:00]
+///     :END:
+fn write_task(contents: &mut String, level: usize, task: &Task) {
+    let mut headline = vec![
+        "*".repeat(level),
+        if task.is_done { "DONE" } else { "TODO" }.to_string(),
+    ];
+    headline.extend(
+        task.priority
+            .map(|priority| format!("[#{}]", priority_letter(priority))),
+    );
+    if !task.description.is_empty() {
+        headline.push(task.description.clone());
+    }
+    if !task.tags.is_empty() {
+        headline.push(format!(":{}:", task.tags.join(":")));
+    }
+    contents.push_str(&headline.join(" "));
+    contents.push('\n');
+
+    let repeater = task.recurrence.as_ref().and_then(repeater);
+    let mut planning = vec![];
+    if task.is_done {
+        planning.extend(
+            task.completed_at
+                .map(|time| format!("CLOSED: {}", inactive_timestamp(time))),
+        );
+    }
+    if let Some(due_date) = task.due_date {
+        let mut timestamp = due_date.format("%Y-%m-%d %a").to_string();
+        if let Some(repeater) = &repeater {
+            timestamp = format!("{} {}", timestamp, repeater);
+        }
+        planning.push(format!("DEADLINE: <{}>", timestamp));
+    }
+    if !planning.is_empty() {
+        contents.push_str(&planning.join(" "));
+        contents.push('\n');
+    }
+
+    let mut properties = vec![];
+    properties.extend(
+        task.created_at
+            .map(|time| ("CREATED", inactive_timestamp(time))),
+    );
+    properties.extend(task.uid.clone().map(|uid| ("ID", uid)));
+    // Recurrences are only kept in the deadline when there's one they fit in
+    if let Some(recurrence) = &task.recurrence {
+        if task.due_date.is_none() || repeater.is_none() {
+            properties.push(("RECURRENCE", recurrence.token()));
+        }
+    }
+    if !properties.is_empty() {
+        contents.push_str(":PROPERTIES:\n");
+        for (key, value) in properties {
+            contents.push_str(&format!("{:<11} {}\n", format!(":{}:", key), value));
+        }
+        contents.push_str(":END:\n");
+    }
+}
+
+/// Writes the groups as headlines, with their tasks as `TODO` and `DONE` headlines under them.
+/// Groups named like `Work/Backend` are written as nested headlines
+pub fn export(task_groups: &[TaskGroup]) -> String {
+    let mut contents = String::new();
+    let mut previous_path: Vec<&str> = vec![];
+
+    for group in task_groups {
+        let path: Vec<&str> = group.name.split(PATH_SEPARATOR).collect();
+        let common = previous_path
+            .iter()
+            .zip(&path)
+            .take_while(|(previous, current)| previous == current)
+            .count();
+
+        // The group's own headline is always written, even if a previous group had the same name
+        for (level, name) in path.iter().enumerate().skip(common.min(path.len() - 1)) {
+            contents.push_str(&format!("{} {}\n", "*".repeat(level + 1), name));
+        }
+        for task in &group.tasks {
+            write_task(&mut contents, path.len() + 1, task);
+        }
+        previous_path = path;
+    }
+
+    contents
+}
+
+/// A headline split into its parts
+struct Headline<'a> {
+    level: usize,
+    /// Whether it's a `TODO` (false) or `DONE` (true) headline. None if it's neither
+    is_done: Option<bool>,
+    priority: Option<Priority>,
+    title: &'a str,
+    tags: Vec<&'a str>,
+}
+
+/// Splits a headline such as `** TODO [#A] Write the report :writing:` into its parts.
+/// Returns None if the line isn't a headline
+fn parse_headline(line: &str) -> Option<Headline<'_>> {
+    let level = line.chars().take_while(|&ch| ch == '*').count();
+    if level == 0 {
+        return None;
+    }
+    let mut rest = line[level..].strip_prefix(' ')?.trim();
+
+    let keyword = [("TODO", false), ("DONE", true)]
+        .iter()
+        .find_map(|&(keyword, is_done)| {
+            let after = rest.strip_prefix(keyword)?;
+            (after.is_empty() || after.starts_with(' ')).then_some((is_done, after))
+        });
+    let is_done = keyword.map(|(is_done, _)| is_done);
+    if let Some((_, after)) = keyword {
+        rest = after.trim_start();
+    }
+
+    let mut priority = None;
+    if let Some(after) = rest.strip_prefix("[#") {
+        if let Some((letter, after)) = after.split_once(']') {
+            priority = parse_priority(letter);
+            if priority.is_some() {
+                rest = after.trim_start();
+            }
+        }
+    }
+
+    let mut tags = vec![];
+    let (title, last_word) = rest.rsplit_once(' ').unwrap_or(("", rest));
+    if last_word.len() > 2 && last_word.starts_with(':') && last_word.ends_with(':') {
+        tags = last_word.split(':').filter(|tag| !tag.is_empty()).collect();
+        rest = title.trim_end();
+    }
+
+    Some(Headline {
+        level,
+        is_done,
+        priority,
+        title: rest,
+        tags,
+    })
+}
+
+/// An org-mode timestamp, such as `<2021-03-04 Thu 10:00 +1w>`
+struct Timestamp<'a> {
+    date: NaiveDate,
+    /// The date and time, if there's a time
+    time: Option<DateTime<Local>>,
+    repeater: Option<&'a str>,
+}
+
+impl Timestamp<'_> {
+    /// Returns the time, or the start of the day if there's no time
+    fn moment(&self) -> Option<DateTime<Local>> {
+        self.time.or_else(|| start_of_day(self.date))
+    }
+}
+
+/// Parses timestamps such as `<2021-03-04 Thu>` or `[2021-02-20 Sat 09:00]`
+fn parse_timestamp(timestamp: &str) -> Result<Timestamp<'_>, String> {
+    let invalid = || format!("`{}` is not a valid timestamp", timestamp);
+    let inner = timestamp
+        .get(1..timestamp.len().saturating_sub(1))
+        .ok_or_else(invalid)?;
+    let mut words = inner.split_whitespace();
+
+    let date = words
+        .next()
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+        .ok_or_else(invalid)?;
+    let mut time = None;
+    let mut repeater = None;
+    for word in words {
+        if word.starts_with('+') || word.starts_with(".+") {
+            repeater = Some(word);
+        } else if let Ok(parsed) =
+            NaiveDateTime::parse_from_str(&format!("{} {}", date, word), "%Y-%m-%d %H:%M")
+        {
+            time = Local.from_local_datetime(&parsed).earliest();
+        }
+    }
+
+    Ok(Timestamp {
+        date,
+        time,
+        repeater,
+    })
+}
+
+/// Splits a planning line such as `CLOSED: [2021-03-02 Tue 18:00] DEADLINE: <2021-03-04 Thu>`
+/// into its keywords and timestamps. Returns None if the line isn't a planning line
+fn parse_planning(line: &str) -> Option<Vec<(&str, &str)>> {
+    let mut entries = vec![];
+    let mut rest = line.trim();
+
+    while !rest.is_empty() {
+        let (keyword, after) = rest.split_once(": ")?;
+        if !["CLOSED", "DEADLINE", "SCHEDULED"].contains(&keyword) {
+            return None;
+        }
+
+        let after = after.trim_start();
+        let closing = match after.chars().next()? {
+            '<' => '>',
+            '[' => ']',
+            _ => return None,
+        };
+        let end = after.find(closing)? + 1;
+        entries.push((keyword, &after[..end]));
+        rest = after[end..].trim_start();
+    }
+
+    Some(entries)
+}
+
+/// Applies a line of a task's section, such as its planning line or one of its properties
+fn apply_line(
+    task: &mut Task,
+    line: &str,
+    scheduled: &mut Option<NaiveDate>,
+) -> Result<(), String> {
+    if let Some(entries) = parse_planning(line) {
+        for (keyword, timestamp) in entries {
+            let timestamp = parse_timestamp(timestamp)?;
+            match keyword {
+                "CLOSED" => task.completed_at = timestamp.moment(),
+                "DEADLINE" => task.due_date = Some(timestamp.date),
+                _ => *scheduled = Some(timestamp.date),
+            }
+            if keyword != "CLOSED" && task.recurrence.is_none() {
+                task.recurrence = timestamp.repeater.and_then(parse_repeater);
+            }
+        }
+        return Ok(());
+    }
+
+    let property = line
+        .trim()
+        .strip_prefix(':')
+        .and_then(|line| line.split_once(':'));
+    match property {
+        Some(("CREATED", value)) => {
+            task.created_at = parse_timestamp(value.trim())?.moment();
+        }
+        Some(("ID", value)) => task.uid = Some(value.trim().into()),
+        Some(("RECURRENCE", value)) => {
+            let value = value.trim();
+            task.recurrence = Some(
+                Recurrence::from_token(value)
+                    .unwrap_or_else(|| Err(format!("`{}` is not a valid recurrence", value)))?,
+            );
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Reads the headlines of an org-mode file. `TODO` and `DONE` headlines are tasks, and other
+/// headlines are groups. Groups nested in other groups are named after the path to them,
+/// e.g. `Work/Backend`, and tasks nested in other tasks are added to their group in order.
+/// Deadlines are the tasks' due dates, and so are scheduled dates for the tasks without one.
+pub fn import(contents: &str) -> Result<Vec<TaskGroup>, String> {
+    let mut task_groups: Vec<TaskGroup> = vec![];
+    // The levels and names of the group headlines the current line is under
+    let mut path: Vec<(usize, &str)> = vec![];
+    // The task being read, with its date for when it's scheduled
+    let mut current: Option<(Task, Option<NaiveDate>)> = None;
+
+    let finish = |current: &mut Option<(Task, Option<NaiveDate>)>,
+                  task_groups: &mut Vec<TaskGroup>,
+                  group_name: String| {
+        if let Some((mut task, scheduled)) = current.take() {
+            task.due_date = task.due_date.or(scheduled);
+            match task_groups
+                .iter_mut()
+                .find(|group| group.name == group_name)
+            {
+                Some(group) => group.tasks.push(task),
+                None => task_groups.push(TaskGroup {
+                    name: group_name,
+                    tasks: vec![task],
+                }),
+            }
+        }
+    };
+    let group_name = |path: &[(usize, &str)]| match path.is_empty() {
+        true => DEFAULT_GROUP.to_string(),
+        false => {
+            let names: Vec<&str> = path.iter().map(|(_, name)| *name).collect();
+            names.join(&PATH_SEPARATOR.to_string())
+        }
+    };
+
+    for (line_idx, line) in contents.lines().enumerate() {
+        let headline = match parse_headline(line) {
+            Some(headline) => headline,
+            None => {
+                if let Some((task, scheduled)) = current.as_mut() {
+                    apply_line(task, line, scheduled)
+                        .map_err(|err| format!("line {}: {}", line_idx + 1, err))?;
+                }
+                continue;
+            }
+        };
+
+        finish(&mut current, &mut task_groups, group_name(&path));
+        match headline.is_done {
+            Some(is_done) => {
+                // Tasks nested in tasks stay in the group of the outer task
+                path.retain(|&(level, _)| level < headline.level);
+                current = Some((
+                    Task {
+                        description: headline.title.into(),
+                        is_done,
+                        priority: headline.priority,
+                        tags: headline.tags.iter().map(|&tag| tag.into()).collect(),
+                        ..Default::default()
+                    },
+                    None,
+                ));
+            }
+            None => {
+                path.retain(|&(level, _)| level < headline.level);
+                path.push((headline.level, headline.title));
+
+                let name = group_name(&path);
+                if !task_groups.iter().any(|group| group.name == name) {
+                    task_groups.push(TaskGroup {
+                        name,
+                        tasks: vec![],
+                    });
+                }
+            }
+        }
+    }
+    finish(&mut current, &mut task_groups, group_name(&path));
+
+    // Headlines that only hold other groups aren't groups themselves
+    let names: Vec<String> = task_groups.iter().map(|group| group.name.clone()).collect();
+    task_groups.retain(|group| {
+        let prefix = format!("{}{}", group.name, PATH_SEPARATOR);
+        !group.tasks.is_empty() || !names.iter().any(|name| name.starts_with(&prefix))
+    });
+
+    Ok(task_groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Weekday;
+
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn time(year: i32, month: u32, day: u32, hour: u32) -> Option<DateTime<Local>> {
+        Local
+            .with_ymd_and_hms(year, month, day, hour, 0, 0)
+            .earliest()
+    }
+
+    fn sample_groups() -> Vec<TaskGroup> {
+        vec![
+            TaskGroup {
+                name: "Work".into(),
+                tasks: vec![
+                    Task {
+                        description: "Write the report".into(),
+                        created_at: time(2021, 2, 20, 9),
+                        due_date: Some(date(2021, 3, 4)),
+                        recurrence: Some(Recurrence::Weekly(vec![])),
+                        priority: Some(Priority::High),
+                        tags: vec!["writing".into(), "q1".into()],
+                        ..Default::default()
+                    },
+                    Task {
+                        description: "Tag the release".into(),
+                        is_done: true,
+                        completed_at: time(2021, 3, 2, 18),
+                        uid: Some("release".into()),
+                        ..Default::default()
+                    },
+                ],
+            },
+            TaskGroup {
+                name: "Work/Backend".into(),
+                tasks: vec![Task {
+                    description: "Review the migrations".into(),
+                    due_date: Some(date(2021, 3, 1)),
+                    recurrence: Some(Recurrence::Weekly(vec![Weekday::Mon, Weekday::Thu])),
+                    ..Default::default()
+                }],
+            },
+            TaskGroup {
+                name: "Chores".into(),
+                tasks: vec![],
+            },
+        ]
+    }
+
+    #[test]
+    fn exporting() {
+        assert_eq!(
+            export(&sample_groups()),
+            "* Work\n\
+             ** TODO [#A] Write the report :writing:q1:\n\
+             DEADLINE: <2021-03-04 Thu +1w>\n\
+             :PROPERTIES:\n\
+             :CREATED:   [2021-02-20 Sat 09:00]\n\
+             :END:\n\
+             ** DONE Tag the release\n\
+             CLOSED: [2021-03-02 Tue 18:00]\n\
+             :PROPERTIES:\n\
+             :ID:        release\n\
+             :END:\n\
+             ** Backend\n\
+             *** TODO Review the migrations\n\
+             DEADLINE: <2021-03-01 Mon>\n\
+             :PROPERTIES:\n\
+             :RECURRENCE: every:mon,thu\n\
+             :END:\n\
+             * Chores\n"
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        assert_eq!(import(&export(&sample_groups())), Ok(sample_groups()));
+    }
+
+    #[test]
+    fn importing() {
+        let contents = "#+TITLE: Notes\n\
+                        Some text before the first headline.\n\
+                        * TODO Buy milk\n\
+                        * Projects\n\
+                        ** Website\n\
+                        *** TODO [#C] Fix the footer :css:\n\
+                        \x20   SCHEDULED: <2021-03-05 Fri 10:00 .+2d>\n\
+                        \x20   Notes about the footer.\n\
+                        **** DONE Pick the colors\n\
+                        *** NEXT is not a known keyword\n";
+        let groups = import(contents).unwrap();
+
+        let names: Vec<&str> = groups.iter().map(|group| group.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                DEFAULT_GROUP,
+                "Projects/Website",
+                "Projects/Website/NEXT is not a known keyword"
+            ]
+        );
+
+        let website = &groups[1].tasks;
+        assert_eq!(website[0].description, "Fix the footer");
+        assert_eq!(website[0].priority, Some(Priority::Low));
+        assert_eq!(website[0].tags, vec!["css".to_string()]);
+        assert_eq!(website[0].due_date, Some(date(2021, 3, 5)));
+        assert_eq!(website[0].recurrence, Some(Recurrence::AfterCompletion(2)));
+        assert_eq!(website[1].description, "Pick the colors");
+        assert!(website[1].is_done);
+
+        // Repeaters with an unknown unit are left out
+        let groups = import("* TODO Water plants\nDEADLINE: <2021-03-04 Thu +1é>").unwrap();
+        assert_eq!(groups[0].tasks[0].due_date, Some(date(2021, 3, 4)));
+        assert_eq!(groups[0].tasks[0].recurrence, None);
+        assert_eq!(parse_repeater("+1000000000w"), None);
+
+        assert_eq!(
+            import("* Work\n** TODO Ship it\nDEADLINE: <2021-13-01 Mon>"),
+            Err("line 3: `<2021-13-01 Mon>` is not a valid timestamp".into())
+        );
+    }
+}
@@ -0,0 +1,366 @@
+use chrono::NaiveDate;
+
+use super::{start_of_day, ConversionOptions, GroupMarker, DEFAULT_GROUP};
+use crate::recurrence::Recurrence;
+use crate::task::{Priority, Task, TaskGroup};
+
+fn priority_letter(priority: Priority) -> char {
+    match priority {
+        Priority::High => 'A',
+        Priority::Medium => 'B',
+        Priority::Low => 'C',
+    }
+}
+
+/// Parses priorities in the `A` format. Every priority below `B` is considered low
+fn parse_priority(letter: &str) -> Option<Priority> {
+    match letter {
+        "A" => Some(Priority::High),
+        "B" => Some(Priority::Medium),
+        letter if letter.len() == 1 && letter.chars().all(|ch| ch.is_ascii_uppercase()) => {
+            Some(Priority::Low)
+        }
+        _ => None,
+    }
+}
+
+/// Returns the name of a `+project` or an `@context`
+fn label(word: &str) -> Option<&str> {
+    word.strip_prefix(|ch| ch == '+' || ch == '@')
+        .filter(|label| !label.is_empty())
+}
+
+fn parse_date(word: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(word, "%Y-%m-%d").ok()
+}
+
+/// Whether a word is written like a date, valid or not, e.g. `2021-13-01`
+fn looks_like_date(word: &str) -> bool {
+    word.len() == 10 && word.chars().all(|ch| ch.is_ascii_digit() || ch == '-')
+}
+
+/// Writes a group's name as the label of a `+project` or an `@context`, which can't contain spaces.
+/// Spaces become `_`, while underscores and backslashes are escaped with a backslash
+fn group_label(name: &str) -> String {
+    let mut label = String::new();
+    for ch in name.chars() {
+        match ch {
+            ' ' => label.push('_'),
+            '_' | '\\' => {
+                label.push('\\');
+                label.push(ch);
+            }
+            ch => label.push(ch),
+        }
+    }
+    label
+}
+
+/// Reads a group's name back from its label, undoing `group_label`
+fn group_name(label: &str) -> String {
+    let mut name = String::new();
+    let mut chars = label.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '_' => name.push(' '),
+            '\\' => name.extend(chars.next()),
+            ch => name.push(ch),
+        }
+    }
+    name
+}
+
+fn group_sigil(options: &ConversionOptions) -> char {
+    match options.group_marker {
+        GroupMarker::Project => '+',
+        GroupMarker::Context => '@',
+    }
+}
+
+/// Writes a task as a todo.txt line, e.g.
+///
+///     x 2021-03-02 2021-02-20 Finish the report +writing due:2021-03-04 pri:A +Work
+///
+/// The group goes last, so that it's told apart from any `@context` in the description.
+///
+/// todo.txt has no way to escape the words it gives a meaning to, so some descriptions can't
+/// be read back as they were: a `+project` in the description becomes a tag, and a task with
+/// neither a priority nor a creation date is read as done if its description starts with `x `
+fn task_line(group: &TaskGroup, task: &Task, options: &ConversionOptions) -> String {
+    let mut words = vec![];
+    let date = |date: NaiveDate| date.format("%Y-%m-%d").to_string();
+
+    if task.is_done {
+        words.push("x".to_string());
+        words.extend(task.completed_at.map(|time| date(time.date_naive())));
+    } else if let Some(priority) = task.priority {
+        words.push(format!("({})", priority_letter(priority)));
+    }
+    // A done task may only have a creation date if it also has a completion date
+    if !task.is_done || task.completed_at.is_some() {
+        words.extend(task.created_at.map(|time| date(time.date_naive())));
+    }
+
+    if !task.description.is_empty() {
+        words.push(task.description.clone());
+    }
+    words.extend(task.tags.iter().map(|tag| format!("+{}", tag)));
+    words.extend(task.due_date.map(|due| format!("due:{}", date(due))));
+    words.extend(task.recurrence.as_ref().map(Recurrence::token));
+    if task.is_done {
+        words.extend(
+            task.priority
+                .map(|priority| format!("pri:{}", priority_letter(priority))),
+        );
+    }
+    words.push(format!(
+        "{}{}",
+        group_sigil(options),
+        group_label(&group.name)
+    ));
+
+    words.join(" ")
+}
+
+/// Writes every task of the given groups as a todo.txt line.
+/// Groups are written as a `+project` or an `@context`, depending on the options
+pub fn export(task_groups: &[TaskGroup], options: &ConversionOptions) -> String {
+    let mut contents = String::new();
+    for group in task_groups {
+        for task in &group.tasks {
+            contents.push_str(&task_line(group, task, options));
+            contents.push('\n');
+        }
+    }
+
+    contents
+}
+
+/// Reads a todo.txt line, returning the task along with the name of its group
+fn parse_line(line: &str, options: &ConversionOptions) -> Result<(String, Task), String> {
+    let mut words = line.split_whitespace().peekable();
+    let mut task = Task::default();
+
+    if words.peek() == Some(&"x") {
+        words.next();
+        task.is_done = true;
+        if let Some(completed_on) = words.peek().and_then(|word| parse_date(word)) {
+            words.next();
+            task.completed_at = start_of_day(completed_on);
+        }
+    } else if let Some(priority) = words
+        .peek()
+        .and_then(|word| word.strip_prefix('(')?.strip_suffix(')'))
+        .and_then(parse_priority)
+    {
+        words.next();
+        task.priority = Some(priority);
+    }
+    if let Some(created_on) = words.peek().and_then(|word| parse_date(word)) {
+        words.next();
+        task.created_at = start_of_day(created_on);
+    }
+
+    // The group is the last of its markers, since markers can also be part of the description
+    let words: Vec<&str> = words.collect();
+    let group_idx = words
+        .iter()
+        .rposition(|word| label(word).is_some() && word.starts_with(group_sigil(options)));
+
+    let mut group = None;
+    let mut description = vec![];
+    for (word_idx, &word) in words.iter().enumerate() {
+        if Some(word_idx) == group_idx {
+            group = label(word).map(group_name);
+        } else if let Some(tag) = label(word).filter(|_| word.starts_with('+')) {
+            if !task.tags.iter().any(|existing| existing == tag) {
+                task.tags.push(tag.into());
+            }
+        } else if let Some(due) = word.strip_prefix("due:").filter(|due| looks_like_date(due)) {
+            task.due_date =
+                Some(parse_date(due).ok_or_else(|| format!("`{}` is not a valid date", due))?);
+        } else if let Some(priority) = word.strip_prefix("pri:").and_then(parse_priority) {
+            task.priority = Some(priority);
+        } else if let Some(recurrence) = Recurrence::from_token(word) {
+            task.recurrence = Some(recurrence?);
+        } else {
+            description.push(word);
+        }
+    }
+
+    task.description = description.join(" ");
+    Ok((group.unwrap_or_else(|| DEFAULT_GROUP.into()), task))
+}
+
+/// Reads the tasks of a todo.txt file, grouping them by project (or context).
+/// Other projects become tags, while other contexts are kept in the description,
+/// as are `due:` words that aren't followed by a date
+pub fn import(contents: &str, options: &ConversionOptions) -> Result<Vec<TaskGroup>, String> {
+    let mut task_groups: Vec<TaskGroup> = vec![];
+
+    for (line_idx, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (group_name, task) =
+            parse_line(line, options).map_err(|err| format!("line {}: {}", line_idx + 1, err))?;
+        match task_groups
+            .iter_mut()
+            .find(|group| group.name == group_name)
+        {
+            Some(group) => group.tasks.push(task),
+            None => task_groups.push(TaskGroup {
+                name: group_name,
+                tasks: vec![task],
+            }),
+        }
+    }
+
+    Ok(task_groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn sample_groups() -> Vec<TaskGroup> {
+        vec![
+            TaskGroup {
+                name: "Side project".into(),
+                tasks: vec![
+                    Task {
+                        description: "Write the changelog".into(),
+                        created_at: start_of_day(date(2021, 2, 20)),
+                        due_date: Some(date(2021, 3, 4)),
+                        priority: Some(Priority::High),
+                        tags: vec!["writing".into()],
+                        ..Default::default()
+                    },
+                    Task {
+                        description: "Tag the release".into(),
+                        is_done: true,
+                        created_at: start_of_day(date(2021, 2, 20)),
+                        completed_at: start_of_day(date(2021, 3, 2)),
+                        priority: Some(Priority::Medium),
+                        ..Default::default()
+                    },
+                ],
+            },
+            TaskGroup {
+                name: "Chores".into(),
+                tasks: vec![
+                    Task {
+                        description: "Water the plants".into(),
+                        recurrence: Some(Recurrence::EveryNDays(3)),
+                        ..Default::default()
+                    },
+                    Task {
+                        description: "Email @bob about it".into(),
+                        ..Default::default()
+                    },
+                ],
+            },
+            TaskGroup {
+                name: "my_group \\ more".into(),
+                tasks: vec![
+                    // The creation date comes first, so the `x` isn't read as a completion mark
+                    Task {
+                        description: "x marks the spot".into(),
+                        created_at: start_of_day(date(2021, 2, 20)),
+                        ..Default::default()
+                    },
+                    Task {
+                        description: "Ask about due:soon".into(),
+                        ..Default::default()
+                    },
+                ],
+            },
+        ]
+    }
+
+    #[test]
+    fn exporting() {
+        let options = ConversionOptions::default();
+        assert_eq!(
+            export(&sample_groups(), &options),
+            "(A) 2021-02-20 Write the changelog +writing due:2021-03-04 +Side_project\n\
+             x 2021-03-02 2021-02-20 Tag the release pri:B +Side_project\n\
+             Water the plants every:3d +Chores\n\
+             Email @bob about it +Chores\n\
+             2021-02-20 x marks the spot +my\\_group_\\\\_more\n\
+             Ask about due:soon +my\\_group_\\\\_more\n"
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        for &group_marker in &[GroupMarker::Project, GroupMarker::Context] {
+            let options = ConversionOptions {
+                group_marker,
+                ..Default::default()
+            };
+            let exported = export(&sample_groups(), &options);
+            assert_eq!(import(&exported, &options), Ok(sample_groups()));
+        }
+    }
+
+    #[test]
+    fn words_that_cant_be_escaped() {
+        let options = ConversionOptions::default();
+        let groups = vec![TaskGroup {
+            name: "Work".into(),
+            tasks: vec![
+                Task {
+                    description: "x marks the spot".into(),
+                    ..Default::default()
+                },
+                Task {
+                    description: "Add a +1 button".into(),
+                    ..Default::default()
+                },
+            ],
+        }];
+
+        let imported = import(&export(&groups, &options), &options).unwrap();
+        let tasks = &imported[0].tasks;
+        assert!(tasks[0].is_done);
+        assert_eq!(tasks[0].description, "marks the spot");
+        assert_eq!(tasks[1].description, "Add a button");
+        assert_eq!(tasks[1].tags, vec!["1".to_string()]);
+    }
+
+    #[test]
+    fn importing() {
+        let options = ConversionOptions {
+            group_marker: GroupMarker::Context,
+            ..Default::default()
+        };
+        let groups = import(
+            "(D) Call mom @phone +family\nx Buy milk\n\n(B) Fix the bike @garage due:2021-13-01",
+            &options,
+        );
+        assert_eq!(
+            groups,
+            Err("line 4: `2021-13-01` is not a valid date".into())
+        );
+
+        let groups = import(
+            "(D) Call mom @phone +family\nx Buy milk\nAsk @bob for the keys @home",
+            &options,
+        )
+        .unwrap();
+        assert_eq!(groups[0].name, "phone");
+        assert_eq!(groups[0].tasks[0].description, "Call mom");
+        assert_eq!(groups[0].tasks[0].priority, Some(Priority::Low));
+        assert_eq!(groups[0].tasks[0].tags, vec!["family".to_string()]);
+        assert_eq!(groups[2].name, "home");
+        assert_eq!(groups[2].tasks[0].description, "Ask @bob for the keys");
+        assert_eq!(groups[1].name, DEFAULT_GROUP);
+        assert!(groups[1].tasks[0].is_done);
+    }
+}
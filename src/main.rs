@@ -1,41 +1,127 @@
 use std::{
     convert::TryFrom,
-    env
+    env,
+    fs::File,
+    io::{self, BufReader},
+    process,
 };
 
 use colored::Colorize;
 
+mod alias;
+mod archive;
+mod batch;
 mod cli;
 mod command;
+mod config;
 mod config_path;
+mod diff;
+mod display;
 mod editor;
 mod errors;
+mod filter;
+mod formats;
 mod macros;
+mod merge;
 mod parser;
+mod recurrence;
 mod repl;
+mod search;
+mod stats;
+mod storage;
+mod sync;
 mod task;
 mod todolist;
+mod watch;
+
+/// Shows what the commands of a dry run changed, in the list and in its configuration
+fn print_changes(before: &todolist::TodoList, after: &todolist::TodoList) {
+    let diff = diff::Diff::between(&before.stored(), &after.stored());
+    print!("{}", diff.with_config(&before.config, &after.config));
+}
+
+/// Saves the list, keeping the changes made elsewhere since it was loaded
+fn save(list: &mut todolist::TodoList) -> Result<(), errors::Error> {
+    match list.save() {
+        Err(errors::Error::ExternalChanges) => list.resolve(todolist::Resolution::Merge),
+        result => result,
+    }
+}
 
 fn main() -> Result<(), errors::Error> {
     
     let cfg_path = unwrap_or_return!(config_path::get_config_path());
-    let mut list = todolist::TodoList::try_from(cfg_path)?;
     if env::args().len() == 1 {
-        print!("{}", list);
+        let list = todolist::TodoList::try_from(cfg_path)?;
+        list.config.apply();
+        list.show(&Default::default());
         return Ok(());
     }
     let matches = cli::get_matches();
     let args = cli::CommandLineArguments::try_from(matches)?;
 
+    let mut list = match args.only_config {
+        true => todolist::TodoList::config_only(cfg_path)?,
+        false => todolist::TodoList::try_from(cfg_path)?,
+    };
+    list.config.apply();
+
+    // In a dry run, the changes are shown instead of being saved
+    list.dry_run = args.dry_run;
+    let before = match args.dry_run {
+        true => Some(list.clone()),
+        false => None,
+    };
+
     for command in args.commands {
         list.evaluate(command);
     }
 
+    if let Some(script) = &args.script {
+        let failed = match script {
+            cli::Script::Stdin => batch::run(&mut list, io::stdin().lock(), args.keep_going)?,
+            cli::Script::File(path) => {
+                let file = BufReader::new(File::open(path)?);
+                batch::run(&mut list, file, args.keep_going)?
+            }
+        };
+
+        match &before {
+            Some(before) => print_changes(before, &list),
+            None if failed == 0 || args.keep_going => save(&mut list)?,
+            None => {}
+        }
+
+        if failed > 0 && !args.keep_going {
+            println!(
+                "{}: stopped at the first failing command. Nothing was saved.",
+                "info".yellow()
+            );
+            process::exit(1);
+        }
+        if failed > 0 {
+            println!("{}: {} command(s) failed.", "info".yellow(), failed);
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
     if args.should_start_repl {
         let mut repl = repl::REPL::new(list)?;
         repl.start_loop()?;
+    } else if let Some(before) = &before {
+        print_changes(before, &list);
+    } else if args.sync {
+        save(&mut list)?;
+        sync::run(&mut list)?;
+    } else if args.watch {
+        save(&mut list)?;
+        watch::run(&mut list, &args.display_options, args.poll)?;
     } else {
-        println!("{}", list);
+        if args.should_show_list {
+            list.show(&args.display_options);
+        }
+        save(&mut list)?;
     }
 
     Ok(())
@@ -0,0 +1,279 @@
+use chrono::{DateTime, Local};
+
+use crate::storage::StoredList;
+use crate::task::{Task, TaskGroup};
+
+/// The result of merging two versions of a todo list that were changed separately
+pub struct Merge {
+    pub list: StoredList,
+    /// How many tasks were changed in both versions. The changes of `ours` were kept for them
+    pub conflicts: usize,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+/// What tells a task apart from the others of its version
+enum Key<'a> {
+    Uid(&'a str),
+    /// Tasks without a UID are matched by description and creation time, since recurring tasks
+    /// leave behind completed occurrences with the same description as the next one
+    Description(&'a str, Option<DateTime<Local>>),
+}
+
+fn key(task: &Task) -> Key<'_> {
+    match &task.uid {
+        Some(uid) => Key::Uid(uid),
+        None => Key::Description(&task.description, task.created_at),
+    }
+}
+
+fn position(tasks: &[Task], key_of: &Key) -> Option<usize> {
+    tasks.iter().position(|task| key(task) == *key_of)
+}
+
+fn group<'a>(list: &'a StoredList, name: &str) -> Option<&'a TaskGroup> {
+    list.task_groups.iter().find(|group| group.name == name)
+}
+
+/// Merges the tasks of a group. Changes are applied to the tasks of `theirs`,
+/// and tasks added in `ours` are placed after the task they followed
+fn merge_tasks(base: &[Task], ours: &[Task], theirs: &[Task], conflicts: &mut usize) -> Vec<Task> {
+    let mut merged = theirs.to_vec();
+
+    // Tasks removed in ours are removed, unless they were changed in theirs
+    for old in base {
+        if position(ours, &key(old)).is_some() {
+            continue;
+        }
+        if let Some(idx) = position(&merged, &key(old)) {
+            match merged[idx] == *old {
+                true => {
+                    merged.remove(idx);
+                }
+                false => *conflicts += 1,
+            }
+        }
+    }
+
+    let mut insert_at = 0;
+    for task in ours {
+        let old = position(base, &key(task)).map(|idx| &base[idx]);
+        match position(&merged, &key(task)) {
+            Some(idx) => {
+                if old != Some(task) {
+                    let changed_in_theirs = matches!(old, Some(old) if merged[idx] != *old);
+                    if changed_in_theirs && merged[idx] != *task {
+                        *conflicts += 1;
+                    }
+                    merged[idx] = task.clone();
+                }
+                insert_at = idx + 1;
+            }
+            // Tasks removed in theirs stay removed, unless they were changed in ours
+            None if old == Some(task) => {}
+            None => {
+                if old.is_some() {
+                    *conflicts += 1;
+                }
+                merged.insert(insert_at, task.clone());
+                insert_at += 1;
+            }
+        }
+    }
+
+    merged
+}
+
+/// Merges `ours` and `theirs`, two versions of a todo list that were both changed from `base`.
+/// Groups are matched by name and tasks by UID or description, so that changes to different
+/// tasks are all kept. When both versions changed the same task, the change of `ours` wins
+pub fn merge(base: &StoredList, ours: &StoredList, theirs: &StoredList) -> Merge {
+    let mut conflicts = 0;
+    let mut task_groups = vec![];
+
+    for their_group in &theirs.task_groups {
+        let base_group = group(base, &their_group.name);
+        match (base_group, group(ours, &their_group.name)) {
+            // Removed in ours
+            (Some(base_group), None) if base_group == their_group => {}
+            (Some(_), None) => {
+                conflicts += 1;
+                task_groups.push(their_group.clone());
+            }
+            (_, Some(our_group)) => {
+                let base_tasks = base_group.map_or(&[][..], |group| &group.tasks);
+                let tasks = merge_tasks(
+                    base_tasks,
+                    &our_group.tasks,
+                    &their_group.tasks,
+                    &mut conflicts,
+                );
+                task_groups.push(TaskGroup {
+                    name: their_group.name.clone(),
+                    tasks,
+                });
+            }
+            (None, None) => task_groups.push(their_group.clone()),
+        }
+    }
+
+    for our_group in &ours.task_groups {
+        if group(theirs, &our_group.name).is_some() {
+            continue;
+        }
+        match group(base, &our_group.name) {
+            // Removed in theirs
+            Some(base_group) if base_group == our_group => {}
+            Some(_) => {
+                conflicts += 1;
+                task_groups.push(our_group.clone());
+            }
+            None => task_groups.push(our_group.clone()),
+        }
+    }
+
+    // Archived tasks are only ever added or restored, so the entries each version
+    // added or removed are applied to the other
+    let mut archive = theirs.archive.clone();
+    archive.entries.retain(|entry| {
+        !base.archive.entries.contains(entry) || ours.archive.entries.contains(entry)
+    });
+    archive.entries.extend(
+        ours.archive
+            .entries
+            .iter()
+            .filter(|entry| !base.archive.entries.contains(entry))
+            .cloned(),
+    );
+
+    let mut saved_queries = theirs.saved_queries.clone();
+    let names = base.saved_queries.keys().chain(ours.saved_queries.keys());
+    for name in names {
+        let our_query = ours.saved_queries.get(name);
+        if our_query == base.saved_queries.get(name) {
+            continue;
+        }
+        match our_query {
+            Some(query) => saved_queries.insert(name.clone(), query.clone()),
+            None => saved_queries.remove(name),
+        };
+    }
+
+    Merge {
+        list: StoredList {
+            task_groups,
+            archive,
+            saved_queries,
+        },
+        conflicts,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(description: &str) -> Task {
+        Task {
+            description: description.into(),
+            ..Default::default()
+        }
+    }
+
+    fn list(groups: &[(&str, &[&str])]) -> StoredList {
+        StoredList {
+            task_groups: groups
+                .iter()
+                .map(|(name, tasks)| TaskGroup {
+                    name: (*name).into(),
+                    tasks: tasks.iter().map(|description| task(description)).collect(),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn merging_separate_changes() {
+        let base = list(&[
+            ("Work", &["Write the report", "Deploy"]),
+            ("Home", &["Cook"]),
+        ]);
+
+        let mut ours = list(&[
+            ("Work", &["Write the report", "Review", "Deploy"]),
+            ("Uni", &["Study"]),
+        ]);
+        ours.task_groups[0].tasks[0].is_done = true;
+        ours.saved_queries
+            .insert("urgent".into(), "due:today".into());
+
+        let theirs = list(&[
+            ("Work", &["Write the report", "Deploy", "Celebrate"]),
+            ("Home", &["Cook", "Clean"]),
+        ]);
+
+        let merge = merge(&base, &ours, &theirs);
+        let mut expected = list(&[
+            (
+                "Work",
+                &["Write the report", "Review", "Deploy", "Celebrate"],
+            ),
+            ("Home", &["Cook", "Clean"]),
+            ("Uni", &["Study"]),
+        ]);
+        expected.task_groups[0].tasks[0].is_done = true;
+        expected.saved_queries = ours.saved_queries.clone();
+
+        assert_eq!(merge.list, expected);
+        // Home was removed in ours but changed in theirs, so it's kept
+        assert_eq!(merge.conflicts, 1);
+    }
+
+    #[test]
+    fn merging_conflicting_changes() {
+        let base = list(&[("Work", &["Write the report", "Deploy"])]);
+
+        let mut ours = base.clone();
+        ours.task_groups[0].tasks[0].is_done = true;
+        ours.task_groups[0].tasks.remove(1);
+
+        let mut theirs = base.clone();
+        theirs.task_groups[0].tasks[0].tags.push("urgent".into());
+        theirs.task_groups[0].tasks[1].is_done = true;
+
+        let merge = merge(&base, &ours, &theirs);
+        assert_eq!(merge.conflicts, 2);
+        assert_eq!(merge.list.task_groups[0].tasks.len(), 2);
+        assert!(merge.list.task_groups[0].tasks[0].tags.is_empty());
+        assert!(merge.list.task_groups[0].tasks[1].is_done);
+    }
+
+    #[test]
+    fn merging_recurring_tasks() {
+        let mut base = list(&[("Home", &[])]);
+        base.task_groups[0]
+            .tasks
+            .push(Task::from_input("Water plants every:3d").unwrap());
+
+        // Completing a recurring task adds its next occurrence, with the same description
+        let mut ours = base.clone();
+        let mut next = ours.task_groups[0].tasks[0].clone();
+        ours.task_groups[0].tasks[0].flip();
+        next.created_at = next
+            .created_at
+            .map(|created_at| created_at + chrono::Duration::seconds(1));
+        ours.task_groups[0].tasks.push(next);
+
+        let mut theirs = base.clone();
+        theirs.task_groups[0].tasks.push(task("Buy soil"));
+
+        let merge = merge(&base, &ours, &theirs);
+        let tasks = &merge.list.task_groups[0].tasks;
+        assert_eq!(merge.conflicts, 0);
+        assert_eq!(tasks.len(), 3);
+        assert!(tasks[0].is_done);
+        assert!(!tasks[1].is_done);
+        assert_eq!(tasks[1].description, "Water plants");
+        assert_eq!(tasks[2].description, "Buy soil");
+    }
+}
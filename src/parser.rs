@@ -1,7 +1,15 @@
 /// Simplistic 'parser' for REPL arguments
+use std::path::PathBuf;
+
 use colored::Colorize;
 
-use crate::command::Command;
+use crate::alias;
+use crate::command::{Command, TaskRef};
+use crate::config::Config;
+use crate::display::DisplayOptions;
+use crate::filter;
+use crate::formats::ConversionOptions;
+use crate::search::{SearchMode, SearchQuery};
 
 fn print_help() -> Command {
     println!("TODO: add help");
@@ -12,7 +20,7 @@ fn print_help() -> Command {
 /// Given a string in the form "x.y", returns Some(x, y) or None
 fn get_tuple<H>(word: &str, help_fn: H) -> Option<(u16, u16)>
 where
-    H: Fn() -> (),
+    H: Fn(),
 {
     let parts = word
         .split(".")
@@ -37,11 +45,61 @@ where
     Some((parts[0], parts[1]))
 }
 
-fn parse_task_flip(words: &Vec<&str>) -> Command {
+/// Parses a single task reference: `G.T`, `G.T-U` or `G.*`
+fn get_task_ref(word: &str) -> Option<TaskRef> {
+    let (group_no, tasks) = {
+        let mut parts = word.splitn(2, '.');
+        (parts.next()?, parts.next()?)
+    };
+    let group_no = group_no.parse::<u16>().ok()?;
+
+    if tasks == "*" {
+        return Some(TaskRef::WholeGroup(group_no));
+    }
+
+    match tasks.find('-') {
+        Some(idx) => {
+            let first = tasks[..idx].parse::<u16>().ok()?;
+            let last = tasks[idx + 1..].parse::<u16>().ok()?;
+            if first > last {
+                return None;
+            }
+            Some(TaskRef::Range(group_no, first, last))
+        }
+        None => {
+            let (group_no, task_no) = get_tuple(word, || {})?;
+            Some(TaskRef::Single(group_no, task_no))
+        }
+    }
+}
+
+/// Given a comma-separated list of task references (e.g. "2.1-5", "2.1,2.4,3.2" or "1.*"),
+/// returns the list of references or None if any of them is malformed
+fn get_task_refs<H>(word: &str, help_fn: H) -> Option<Vec<TaskRef>>
+where
+    H: Fn(),
+{
+    let refs: Option<Vec<TaskRef>> = word
+        .split(',')
+        .filter(|x| !x.is_empty())
+        .map(get_task_ref)
+        .collect();
+
+    match refs {
+        Some(refs) if !refs.is_empty() => Some(refs),
+        _ => {
+            help_fn();
+            None
+        }
+    }
+}
+
+fn parse_task_flip(words: &[&str]) -> Command {
     // Example: done 2.3
     if words.len() != 2 {
         println!("{}: wrong arguments to `{}`", "error".red(), "done".green());
         println!("Example usage: 'done 2.3', in order to mark the second task of the third group as done");
+        return Command::NoOp;
     }
 
     let task_flip_help = || {
@@ -50,16 +108,24 @@ fn parse_task_flip(words: &Vec<&str>) -> Command {
             "error".red(),
             "done".green()
         );
-        println!("Argument should follow the format `T.G`, where T represents the task number and G the group number.");
+        println!("Argument should follow the format `G.T`, where G represents the group number and T the task number.");
+        println!(
+            "Ranges (`2.1-5`), lists (`2.1,2.4,3.2`) and whole groups (`1.*`) are also accepted."
+        );
     };
 
-    match get_tuple(words[1], task_flip_help) {
-        Some((group_number, task_number)) => Command::FlipTask(group_number, task_number),
+    match get_task_refs(words[1], task_flip_help) {
+        Some(refs) => match refs.as_slice() {
+            [TaskRef::Single(group_number, task_number)] => {
+                Command::FlipTask(*group_number, *task_number)
+            }
+            _ => Command::FlipTasks(refs),
+        },
         None => Command::NoOp,
     }
 }
 
-fn parse_task_addition(words: &Vec<&str>) -> Command {
+fn parse_task_addition(words: &[&str]) -> Command {
     // Example: add 2 "hahaha"
     if words.len() < 3 {
         println!(
@@ -84,7 +150,7 @@ fn parse_task_addition(words: &Vec<&str>) -> Command {
     Command::AddTask(task_description, group_no)
 }
 
-fn parse_group_addition(words: &Vec<&str>) -> Command {
+fn parse_group_addition(words: &[&str]) -> Command {
     // Example: "group University"
 
     if words.len() < 2 {
@@ -102,7 +168,7 @@ fn parse_group_addition(words: &Vec<&str>) -> Command {
     Command::AddGroup(group_name)
 }
 
-fn parse_removal(words: &Vec<&str>) -> Command {
+fn parse_removal(words: &[&str]) -> Command {
     // Examples:
     //     remove task 3.2
     //     remove group 2
@@ -112,6 +178,7 @@ fn parse_removal(words: &Vec<&str>) -> Command {
             "error".red(),
             "remove".green()
         );
+        return Command::NoOp;
     }
 
     // TODO: add usage help
@@ -130,8 +197,11 @@ fn parse_removal(words: &Vec<&str>) -> Command {
             Some(group_no) => Command::RemoveGroup(group_no),
             None => Command::NoOp,
         },
-        "task" => match get_tuple(words[2], print_usage) {
-            Some((task_no, group_no)) => Command::RemoveTask(task_no, group_no),
+        "task" => match get_task_refs(words[2], print_usage) {
+            Some(refs) => match refs.as_slice() {
+                [TaskRef::Single(group_no, task_no)] => Command::RemoveTask(*group_no, *task_no),
+                _ => Command::RemoveTasks(refs),
+            },
             None => Command::NoOp,
         },
         other => {
@@ -147,7 +217,369 @@ fn parse_removal(words: &Vec<&str>) -> Command {
     }
 }
 
-// fn parse_addition(words: &Vec<&str>) -> Command {
+fn parse_clean(words: &[&str]) -> Command {
+    // Examples:
+    //     clean
+    //     clean 2
+    match words.get(1) {
+        None => Command::Clean(None),
+        Some(word) => match word.parse::<u16>() {
+            Ok(group_no) => Command::Clean(Some(group_no)),
+            Err(err) => {
+                println!("{}: {}", "error".red(), err);
+                println!("Example usage: 'clean 2', in order to archive the done tasks of the second group.");
+                Command::NoOp
+            }
+        },
+    }
+}
+
+fn parse_restore(words: &[&str]) -> Command {
+    // Example: restore 3
+    if words.len() != 2 {
+        println!(
+            "{}: wrong arguments to `{}`",
+            "error".red(),
+            "restore".green()
+        );
+        println!(
+            "Example usage: 'restore 3', in order to restore the third entry of `show archive`."
+        );
+        return Command::NoOp;
+    }
+
+    match words[1].parse::<u16>() {
+        Ok(entry_no) => Command::Restore(entry_no),
+        Err(err) => {
+            println!("{}: {}", "error".red(), err);
+            Command::NoOp
+        }
+    }
+}
+
+fn parse_find(words: &[&str]) -> Command {
+    // Examples:
+    //     find review
+    //     find -i deploy review
+    //     find -r ^(Deploy|Review)
+    //     find -f dplyrvw
+    let mut query = SearchQuery {
+        pattern: String::new(),
+        mode: SearchMode::Text,
+        ignore_case: false,
+    };
+
+    let mut words = words[1..].iter().peekable();
+    while let Some(flag) = words.peek() {
+        match **flag {
+            "-i" => query.ignore_case = true,
+            "-r" => query.mode = SearchMode::Regex,
+            "-f" => query.mode = SearchMode::Fuzzy,
+            _ => break,
+        }
+        words.next();
+    }
+
+    query.pattern = words.copied().collect::<Vec<&str>>().join(" ");
+
+    if query.pattern.is_empty() {
+        println!(
+            "{}: missing search pattern to `{}`",
+            "error".red(),
+            "find".green()
+        );
+        println!("Example usage: 'find -i review', in order to find every task mentioning \"review\", ignoring case.");
+        println!("Use `-r` to search with a regular expression and `-f` to search fuzzily.");
+        return Command::NoOp;
+    }
+
+    if let Err(err) = query.validate() {
+        println!("{}: {}", "error".red(), err);
+        return Command::NoOp;
+    }
+
+    Command::Find(query)
+}
+
+fn parse_show(words: &[&str]) -> Command {
+    // Examples:
+    //     show
+    //     show dates
+    //     show archive
+    //     show pending group:Uni due<7d +backend priority>=high
+    //     show @weekly
+    //     show sort:due flat
+    //     show pending sort:priority reverse
+    //     show hide-done collapse limit:5
+    if words.get(1) == Some(&"archive") {
+        return Command::ShowArchive;
+    }
+
+    let mut options = DisplayOptions::default();
+    let mut query = vec![];
+    for word in &words[1..] {
+        match options.apply(word) {
+            Some(Ok(())) => {}
+            Some(Err(err)) => {
+                println!("{}: {}", "error".red(), err);
+                return Command::NoOp;
+            }
+            None => query.push(*word),
+        }
+    }
+
+    if !query.is_empty() {
+        match filter::parse(&query) {
+            Ok(filter) => options.filter = Some(filter),
+            Err(err) => {
+                println!("{}: invalid query: {}", "error".red(), err);
+                println!("Example usage: 'show pending group:Uni due<7d +backend priority>=high'");
+                return Command::NoOp;
+            }
+        }
+    }
+
+    Command::ShowList(options)
+}
+
+fn parse_display(words: &[&str]) -> Command {
+    // Examples:
+    //     display
+    //     display hide-done collapse limit:5
+    //     display reset
+    let print_usage = || {
+        println!("Example usage: 'display hide-done limit:5', in order to hide done tasks and show at most 5 tasks per group by default.");
+        println!("Accepted options are `hide-done`, `all`, `counts`, `collapse`, `expand`, `limit:N` and `reset`.");
+    };
+
+    match words.get(1) {
+        None => return Command::ShowDisplaySettings,
+        Some(&"reset") if words.len() == 2 => {
+            return Command::SetDisplaySettings(DisplayOptions {
+                hide_done: Some(false),
+                counts_only: Some(false),
+                collapse_done: Some(false),
+                limit: Some(0),
+                ..Default::default()
+            })
+        }
+        _ => {}
+    }
+
+    let mut options = DisplayOptions::default();
+    for word in &words[1..] {
+        // These only make sense for a single `show`
+        let is_per_show =
+            matches!(*word, "dates" | "flat" | "reverse") || word.starts_with("sort:");
+
+        match options.apply(word) {
+            Some(Ok(())) if !is_per_show => {}
+            Some(Err(err)) => {
+                println!("{}: {}", "error".red(), err);
+                return Command::NoOp;
+            }
+            _ => {
+                println!(
+                    "{}: `{}` cannot be set as a default display option",
+                    "error".red(),
+                    word
+                );
+                print_usage();
+                return Command::NoOp;
+            }
+        }
+    }
+
+    Command::SetDisplaySettings(options)
+}
+
+fn parse_query(words: &[&str]) -> Command {
+    // Examples:
+    //     query save weekly pending due<7d
+    //     query remove weekly
+    //     query list
+    let print_usage = || {
+        println!("Example usage: 'query save weekly pending due<7d', in order to be able to run `show @weekly`.");
+        println!(
+            "Saved queries are listed with `query list` and removed with `query remove weekly`."
+        );
+    };
+
+    match words.get(1).copied() {
+        None | Some("list") => Command::ListQueries,
+        Some("save") if words.len() >= 4 => {
+            let name = words[2].trim_start_matches('@');
+            Command::SaveQuery(name.into(), words[3..].join(" "))
+        }
+        Some("remove") if words.len() == 3 => {
+            Command::RemoveQuery(words[2].trim_start_matches('@').into())
+        }
+        _ => {
+            println!(
+                "{}: wrong arguments to `{}`",
+                "error".red(),
+                "query".green()
+            );
+            print_usage();
+            Command::NoOp
+        }
+    }
+}
+
+fn parse_config(words: &[&str]) -> Command {
+    // Examples:
+    //     config
+    //     config get date_format
+    //     config set prompt dodo>
+    let print_usage = || {
+        println!("Example usage: 'config set date_format %d/%m/%Y', in order to change how dates are shown.");
+        println!("Every key is listed with `config list`, and read with `config get date_format`.");
+    };
+
+    match words.get(1).copied() {
+        None | Some("list") if words.len() <= 2 => Command::ConfigList,
+        Some("get") if words.len() == 3 => Command::ConfigGet(words[2].into()),
+        Some("set") if words.len() >= 4 => {
+            Command::ConfigSet(words[2].into(), words[3..].join(" "))
+        }
+        _ => {
+            println!(
+                "{}: wrong arguments to `{}`",
+                "error".red(),
+                "config".green()
+            );
+            print_usage();
+            Command::NoOp
+        }
+    }
+}
+
+fn parse_alias(words: &[&str]) -> Command {
+    // Examples:
+    //     alias
+    //     alias x done
+    //     alias remove x
+    let print_usage = || {
+        println!("Example usage: 'alias x done', in order to be able to run `x 1.2`.");
+        println!("Aliases and macros are listed with `alias` and removed with `alias remove x`.");
+    };
+
+    match words.get(1).copied() {
+        None => Command::ListAliases,
+        Some("remove") if words.len() == 3 => Command::RemoveAlias(words[2].into()),
+        Some(name) if words.len() >= 3 => Command::SetAlias(name.into(), words[2..].join(" ")),
+        _ => {
+            println!(
+                "{}: wrong arguments to `{}`",
+                "error".red(),
+                "alias".green()
+            );
+            print_usage();
+            Command::NoOp
+        }
+    }
+}
+
+fn parse_macro(words: &[&str]) -> Command {
+    // Examples:
+    //     macro ship done $1 ; task 2 Announce $2
+    //     macro remove ship
+    let print_usage = || {
+        println!("Example usage: 'macro ship done $1 ; task 2 Announce $2', in order to be able to run `ship 1.3 v1.0`.");
+        println!("`$1`, `$2`, ... are replaced by the macro's arguments, and `$@` by all of them.");
+    };
+
+    let lines: Vec<String> = words
+        .get(2..)
+        .unwrap_or_default()
+        .join(" ")
+        .split(';')
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect();
+
+    match words.get(1).copied() {
+        Some("remove") if words.len() == 3 => Command::RemoveAlias(words[2].into()),
+        Some(name) if !lines.is_empty() => Command::SetMacro(name.into(), lines),
+        _ => {
+            println!(
+                "{}: wrong arguments to `{}`",
+                "error".red(),
+                "macro".green()
+            );
+            print_usage();
+            Command::NoOp
+        }
+    }
+}
+
+/// Parses the arguments to `export` and `import`: options, a format name and a file path,
+/// in any order. Returns None if they're not valid.
+fn parse_conversion(words: &[&str]) -> Option<(ConversionOptions, Option<PathBuf>)> {
+    let mut options = ConversionOptions::default();
+    let mut path = None;
+
+    for word in &words[1..] {
+        match options.apply(word) {
+            Some(Ok(())) => continue,
+            Some(Err(err)) => {
+                println!("{}: {}", "error".red(), err);
+                return None;
+            }
+            None => {}
+        }
+
+        match word.parse() {
+            Ok(format) if options.format.is_none() => options.format = Some(format),
+            _ if path.is_none() => path = Some(PathBuf::from(word)),
+            _ => {
+                println!("{}: unexpected argument `{}`", "error".red(), word);
+                return None;
+            }
+        }
+    }
+
+    Some((options, path))
+}
+
+fn parse_export(words: &[&str]) -> Command {
+    // Examples:
+    //     export todotxt
+    //     export todo.txt group-as:context
+    //     export ~/tasks todotxt
+    match parse_conversion(words) {
+        Some((options, path)) => Command::Export(options, path),
+        None => {
+            println!("Example usage: 'export todo.txt', in order to write the list to `todo.txt` in the todo.txt format.");
+            Command::NoOp
+        }
+    }
+}
+
+fn parse_import(words: &[&str]) -> Command {
+    // Examples:
+    //     import todo.txt
+    //     import todo.txt group-as:context
+    //     import replace todo.txt
+    // `replace` is only a flag right after `import`, so that a file named `replace` can be imported
+    let replace = words.len() > 2 && words[1] == "replace";
+    let words = match replace {
+        true => [&words[..1], &words[2..]].concat(),
+        false => words.to_vec(),
+    };
+
+    match parse_conversion(&words) {
+        Some((options, Some(path))) => Command::Import(options, path, replace),
+        _ => {
+            println!("Example usage: 'import todo.txt', in order to add the tasks of `todo.txt` to the list.");
+            println!("Write 'import replace todo.txt' to replace the whole list instead.");
+            Command::NoOp
+        }
+    }
+}
+
+// fn parse_addition(words: &[&str]) -> Command {
 //     // Examples:
 //     //     add task 3.2
 //     //     add group 2
@@ -192,7 +624,8 @@ fn parse_removal(words: &Vec<&str>) -> Command {
 //     }
 // }
 
-pub fn parse(line: &str) -> Command {
+/// Parses a line into one of the built-in commands, without expanding aliases
+pub fn parse_command(line: &str) -> Command {
     let words: Vec<&str> = line.split(' ').filter(|x| !x.is_empty()).collect();
     if words.is_empty() {
         return Command::NoOp;
@@ -205,8 +638,21 @@ pub fn parse(line: &str) -> Command {
         word if word.starts_with("group") => parse_group_addition(&words),
         word if word.starts_with("done") => parse_task_flip(&words),
         // word if word.starts_with("add") => parse_addition(&words),
-        // TODO: add subcommands to `show`
-        word if word.starts_with("show") => Command::ShowList,
+        word if word.starts_with("clean") => parse_clean(&words),
+        word if word.starts_with("restore") => parse_restore(&words),
+        word if word.starts_with("find") => parse_find(&words),
+        word if word.starts_with("query") => parse_query(&words),
+        word if word.starts_with("display") => parse_display(&words),
+        word if word.starts_with("config") => parse_config(&words),
+        word if word.starts_with("alias") => parse_alias(&words),
+        word if word.starts_with("export") => parse_export(&words),
+        word if word.starts_with("import") => parse_import(&words),
+        word if word.starts_with("macro") => parse_macro(&words),
+        word if word.starts_with("stats") => Command::ShowStats,
+        word if word.starts_with("save") => Command::Save,
+        word if word.starts_with("reload") => Command::Reload(word.ends_with('!')),
+        word if word.starts_with("quit") => Command::Quit(word.ends_with('!')),
+        word if word.starts_with("show") => parse_show(&words),
         word => {
             println!(
                 "{}: \"{}\" is not a recognized command. \nType in `help` to get additional help.",
@@ -218,9 +664,28 @@ pub fn parse(line: &str) -> Command {
     }
 }
 
+/// Parses a line after expanding the user's aliases and macros, which may turn it into several commands.
+/// Lines defining or removing aliases and macros are never expanded, so that any of them can be removed
+pub fn parse(line: &str, config: &Config) -> Vec<Command> {
+    let first_word = line.split(' ').find(|x| !x.is_empty()).unwrap_or_default();
+    if first_word.starts_with("alias") || first_word.starts_with("macro") {
+        return vec![parse_command(line)];
+    }
+
+    match alias::expand(line, config) {
+        Ok(lines) => lines.iter().map(|line| parse_command(line)).collect(),
+        Err(err) => {
+            println!("{}: {}. Ignoring command.", "error".red(), err);
+            vec![]
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::display::SortKey;
+    use crate::filter::{Comparison, DateOperand, Filter};
 
     #[test]
     fn tuple_getting() {
@@ -236,7 +701,7 @@ mod tests {
 
         assert_eq!(
             Command::AddTask("\"Mop the floor\"".into(), 3,),
-            parse(line)
+            parse_command(line)
         );
     }
 
@@ -244,7 +709,10 @@ mod tests {
     fn task_addition_2() {
         let line = "task 1 \"Read a book\"";
 
-        assert_eq!(Command::AddTask("\"Read a book\"".into(), 1,), parse(line));
+        assert_eq!(
+            Command::AddTask("\"Read a book\"".into(), 1,),
+            parse_command(line)
+        );
     }
 
     #[test]
@@ -254,7 +722,7 @@ mod tests {
         assert_eq!(
             // Should fail upon group number parsing and return NoOp
             Command::NoOp,
-            parse(line)
+            parse_command(line)
         );
     }
 
@@ -262,21 +730,268 @@ mod tests {
     fn group_addition() {
         let line = "group University";
 
-        assert_eq!(parse(line), Command::AddGroup("University".into(),))
+        assert_eq!(parse_command(line), Command::AddGroup("University".into(),))
     }
 
     #[test]
     fn task_flip() {
-        assert_eq!(parse("done 2.3"), Command::FlipTask(2, 3,))
+        assert_eq!(parse_command("done 2.3"), Command::FlipTask(2, 3,))
     }
 
     #[test]
     fn task_removal() {
-        assert_eq!(parse("remove task 2.3"), Command::RemoveTask(2, 3));
+        assert_eq!(parse_command("remove task 2.3"), Command::RemoveTask(2, 3));
+    }
+
+    #[test]
+    fn task_flip_range() {
+        assert_eq!(
+            parse_command("done 2.1-5"),
+            Command::FlipTasks(vec![TaskRef::Range(2, 1, 5)])
+        )
+    }
+
+    #[test]
+    fn task_flip_list() {
+        assert_eq!(
+            parse_command("done 2.1,2.4,3.2"),
+            Command::FlipTasks(vec![
+                TaskRef::Single(2, 1),
+                TaskRef::Single(2, 4),
+                TaskRef::Single(3, 2)
+            ])
+        )
+    }
+
+    #[test]
+    fn task_flip_bad_ref() {
+        assert_eq!(parse_command("done 2.1,x.4"), Command::NoOp);
+        assert_eq!(parse_command("done 2.5-1"), Command::NoOp);
+    }
+
+    #[test]
+    fn whole_group_removal() {
+        assert_eq!(
+            parse_command("remove task 1.*"),
+            Command::RemoveTasks(vec![TaskRef::WholeGroup(1)])
+        );
+    }
+
+    #[test]
+    fn clean() {
+        assert_eq!(parse_command("clean"), Command::Clean(None));
+        assert_eq!(parse_command("clean 2"), Command::Clean(Some(2)));
+    }
+
+    #[test]
+    fn show() {
+        assert_eq!(
+            parse_command("show"),
+            Command::ShowList(DisplayOptions::default())
+        );
+        assert_eq!(
+            parse_command("show dates"),
+            Command::ShowList(DisplayOptions {
+                show_timestamps: true,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn find() {
+        assert_eq!(
+            parse_command("find -i -f deploy review"),
+            Command::Find(SearchQuery {
+                pattern: "deploy review".into(),
+                mode: SearchMode::Fuzzy,
+                ignore_case: true,
+            })
+        );
+        assert_eq!(parse_command("find"), Command::NoOp);
+        assert_eq!(parse_command("find -r ("), Command::NoOp);
+    }
+
+    #[test]
+    fn show_query() {
+        assert_eq!(
+            parse_command("show dates pending due<=7d"),
+            Command::ShowList(DisplayOptions {
+                show_timestamps: true,
+                filter: Some(Filter::And(vec![
+                    Filter::Pending,
+                    Filter::Due(Comparison::LessOrEqual, DateOperand::DaysFromToday(7))
+                ])),
+                ..Default::default()
+            })
+        );
+        assert_eq!(parse_command("show or"), Command::NoOp);
+    }
+
+    #[test]
+    fn show_sorted() {
+        assert_eq!(
+            parse_command("show sort:due flat reverse"),
+            Command::ShowList(DisplayOptions {
+                sort: Some(SortKey::Due),
+                reverse: true,
+                flat: true,
+                ..Default::default()
+            })
+        );
+        assert_eq!(parse_command("show sort:size"), Command::NoOp);
+    }
+
+    #[test]
+    fn show_compact() {
+        assert_eq!(
+            parse_command("show hide-done collapse limit:3 pending"),
+            Command::ShowList(DisplayOptions {
+                hide_done: Some(true),
+                collapse_done: Some(true),
+                limit: Some(3),
+                filter: Some(Filter::Pending),
+                ..Default::default()
+            })
+        );
+        assert_eq!(parse_command("show limit:few"), Command::NoOp);
+    }
+
+    #[test]
+    fn display_settings() {
+        assert_eq!(parse_command("display"), Command::ShowDisplaySettings);
+        assert_eq!(
+            parse_command("display counts all"),
+            Command::SetDisplaySettings(DisplayOptions {
+                counts_only: Some(true),
+                hide_done: Some(false),
+                ..Default::default()
+            })
+        );
+        assert_eq!(parse_command("display sort:due"), Command::NoOp);
+        assert_eq!(parse_command("display pending"), Command::NoOp);
+    }
+
+    #[test]
+    fn saved_queries() {
+        assert_eq!(
+            parse_command("query save weekly pending due<7d"),
+            Command::SaveQuery("weekly".into(), "pending due<7d".into())
+        );
+        assert_eq!(
+            parse_command("query remove @weekly"),
+            Command::RemoveQuery("weekly".into())
+        );
+        assert_eq!(parse_command("query"), Command::ListQueries);
+        assert_eq!(parse_command("query save weekly"), Command::NoOp);
+    }
+
+    #[test]
+    fn config() {
+        assert_eq!(parse_command("config"), Command::ConfigList);
+        assert_eq!(
+            parse_command("config get display.limit"),
+            Command::ConfigGet("display.limit".into())
+        );
+        assert_eq!(
+            parse_command("config set date_format %d %b %Y"),
+            Command::ConfigSet("date_format".into(), "%d %b %Y".into())
+        );
+        assert_eq!(parse_command("config set prompt"), Command::NoOp);
+    }
+
+    #[test]
+    fn aliases() {
+        assert_eq!(parse_command("alias"), Command::ListAliases);
+        assert_eq!(
+            parse_command("alias rm remove"),
+            Command::SetAlias("rm".into(), "remove".into())
+        );
+        assert_eq!(
+            parse_command("macro ship done $1 ; task 2 Announce $2;"),
+            Command::SetMacro(
+                "ship".into(),
+                vec!["done $1".into(), "task 2 Announce $2".into()]
+            )
+        );
+        assert_eq!(
+            parse_command("macro remove ship"),
+            Command::RemoveAlias("ship".into())
+        );
+        assert_eq!(parse_command("macro ship ;"), Command::NoOp);
+
+        let mut config = Config::default();
+        config.set_alias("x".into(), "done".into()).unwrap();
+        assert_eq!(parse("x 1.2", &config), vec![Command::FlipTask(1, 2)]);
+
+        config.set_alias("alias".into(), "show".into()).unwrap();
+        assert_eq!(parse("alias", &config), vec![Command::ListAliases]);
+        assert_eq!(
+            parse("alias remove alias", &config),
+            vec![Command::RemoveAlias("alias".into())]
+        );
+    }
+
+    #[test]
+    fn conversions() {
+        use crate::formats::{Format, GroupMarker};
+
+        assert_eq!(
+            parse_command("export todotxt"),
+            Command::Export(
+                ConversionOptions {
+                    format: Some(Format::TodoTxt),
+                    ..Default::default()
+                },
+                None
+            )
+        );
+        assert_eq!(
+            parse_command("import replace tasks.txt group-as:context"),
+            Command::Import(
+                ConversionOptions {
+                    group_marker: GroupMarker::Context,
+                    ..Default::default()
+                },
+                "tasks.txt".into(),
+                true
+            )
+        );
+        assert_eq!(
+            parse_command("import replace"),
+            Command::Import(ConversionOptions::default(), "replace".into(), false)
+        );
+        assert_eq!(
+            parse_command("import replace replace"),
+            Command::Import(ConversionOptions::default(), "replace".into(), true)
+        );
+        assert_eq!(parse_command("import tasks.txt replace"), Command::NoOp);
+        assert_eq!(parse_command("import"), Command::NoOp);
+        assert_eq!(parse_command("export a.txt b.txt"), Command::NoOp);
+    }
+
+    #[test]
+    fn stats() {
+        assert_eq!(parse_command("stats"), Command::ShowStats);
+    }
+
+    #[test]
+    fn session() {
+        assert_eq!(parse_command("save"), Command::Save);
+        assert_eq!(parse_command("reload"), Command::Reload(false));
+        assert_eq!(parse_command("reload!"), Command::Reload(true));
+        assert_eq!(parse_command("quit"), Command::Quit(false));
+        assert_eq!(parse_command("quit!"), Command::Quit(true));
+    }
+
+    #[test]
+    fn archive() {
+        assert_eq!(parse_command("show archive"), Command::ShowArchive);
+        assert_eq!(parse_command("restore 3"), Command::Restore(3));
     }
 
     #[test]
     fn group_removal() {
-        assert_eq!(parse("remove group 2"), Command::RemoveGroup(2));
+        assert_eq!(parse_command("remove group 2"), Command::RemoveGroup(2));
     }
 }
@@ -0,0 +1,245 @@
+use std::{fmt, str::FromStr};
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+/// How often a task repeats
+pub enum Recurrence {
+    /// Every day. Syntax: `every:day`
+    Daily,
+    /// On the given days of the week. Syntax: `every:mon,thu`
+    Weekly(Vec<Weekday>),
+    /// On the same day of every month. Syntax: `every:month`
+    Monthly,
+    /// Every N days. Syntax: `every:3d`
+    EveryNDays(u32),
+    /// N days after the task was last completed. Syntax: `after:3d`
+    AfterCompletion(u32),
+}
+
+/// Parses strings in the format "Nd", e.g. "3d"
+fn parse_days(word: &str) -> Option<u32> {
+    word.strip_suffix('d')?
+        .parse::<u32>()
+        .ok()
+        .filter(|&days| days > 0)
+}
+
+fn weekday_name(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "mon",
+        Weekday::Tue => "tue",
+        Weekday::Wed => "wed",
+        Weekday::Thu => "thu",
+        Weekday::Fri => "fri",
+        Weekday::Sat => "sat",
+        Weekday::Sun => "sun",
+    }
+}
+
+/// Adds a month to the given date, clamping the day to the end of the next month if needed
+fn add_month(date: NaiveDate) -> NaiveDate {
+    let (year, month) = match date.month() {
+        12 => (date.year() + 1, 1),
+        month => (date.year(), month + 1),
+    };
+
+    (1..=date.day())
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+        .unwrap_or(date)
+}
+
+impl Recurrence {
+    /// Parses a recurrence token as typed by the user, e.g. `every:mon,thu` or `after:3d`.
+    /// Returns None if the word isn't a recurrence token at all.
+    pub fn from_token(word: &str) -> Option<Result<Self, String>> {
+        if let Some(rule) = word.strip_prefix("every:") {
+            return Some(rule.parse());
+        }
+
+        word.strip_prefix("after:").map(|rule| {
+            parse_days(rule)
+                .map(Recurrence::AfterCompletion)
+                .ok_or_else(|| format!("expected `after:Nd`, found `{}`", word))
+        })
+    }
+
+    /// Returns the token this recurrence is typed as, e.g. `every:mon,thu` or `after:3d`
+    pub fn token(&self) -> String {
+        match self {
+            Recurrence::Daily => "every:day".into(),
+            Recurrence::Weekly(weekdays) if weekdays.is_empty() => "every:week".into(),
+            Recurrence::Weekly(weekdays) => {
+                let names: Vec<&str> = weekdays.iter().map(|day| weekday_name(*day)).collect();
+                format!("every:{}", names.join(","))
+            }
+            Recurrence::Monthly => "every:month".into(),
+            Recurrence::EveryNDays(days) => format!("every:{}d", days),
+            Recurrence::AfterCompletion(days) => format!("after:{}d", days),
+        }
+    }
+
+    /// Returns the due date of the occurrence after the one due on `due_date`
+    /// (or completed on `completed_on`, for `AfterCompletion`).
+    /// Occurrences that would already be overdue on `completed_on` are skipped.
+    pub fn next_due_date(&self, due_date: Option<NaiveDate>, completed_on: NaiveDate) -> NaiveDate {
+        if let Recurrence::AfterCompletion(days) = self {
+            return completed_on + Duration::days(*days as i64);
+        }
+
+        let mut next = self.advance(due_date.unwrap_or(completed_on));
+        while next <= completed_on {
+            next = self.advance(next);
+        }
+        next
+    }
+
+    fn advance(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Recurrence::Daily => date + Duration::days(1),
+            Recurrence::Weekly(weekdays) if weekdays.is_empty() => date + Duration::days(7),
+            Recurrence::Weekly(weekdays) => (1..=7)
+                .map(|offset| date + Duration::days(offset))
+                .find(|day| weekdays.contains(&day.weekday()))
+                .unwrap_or(date + Duration::days(7)),
+            Recurrence::Monthly => add_month(date),
+            Recurrence::EveryNDays(days) | Recurrence::AfterCompletion(days) => {
+                date + Duration::days(*days as i64)
+            }
+        }
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = String;
+
+    /// Parses the part after `every:`
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        match rule {
+            "day" | "daily" => return Ok(Recurrence::Daily),
+            "week" | "weekly" => return Ok(Recurrence::Weekly(vec![])),
+            "month" | "monthly" => return Ok(Recurrence::Monthly),
+            _ => {}
+        }
+
+        if let Some(days) = parse_days(rule) {
+            return Ok(Recurrence::EveryNDays(days));
+        }
+
+        let weekdays: Result<Vec<Weekday>, _> = rule
+            .split(',')
+            .map(|day| day.parse::<Weekday>().map_err(|_| day))
+            .collect();
+
+        match weekdays {
+            Ok(mut weekdays) => {
+                weekdays.sort_by_key(|day| day.num_days_from_monday());
+                weekdays.dedup();
+                Ok(Recurrence::Weekly(weekdays))
+            }
+            Err(day) => Err(format!(
+                "`{}` is not a valid recurrence. Expected `day`, `week`, `month`, `Nd` or weekdays such as `mon,thu`",
+                day
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Recurrence::Daily => write!(f, "every day"),
+            Recurrence::Weekly(weekdays) if weekdays.is_empty() => write!(f, "every week"),
+            Recurrence::Weekly(weekdays) => {
+                let names: Vec<&str> = weekdays.iter().map(|day| weekday_name(*day)).collect();
+                write!(f, "every {}", names.join(","))
+            }
+            Recurrence::Monthly => write!(f, "every month"),
+            Recurrence::EveryNDays(days) => write!(f, "every {}d", days),
+            Recurrence::AfterCompletion(days) => write!(f, "{}d after completion", days),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn parsing() {
+        assert_eq!(
+            Recurrence::from_token("every:thu,mon"),
+            Some(Ok(Recurrence::Weekly(vec![Weekday::Mon, Weekday::Thu])))
+        );
+        assert_eq!(
+            Recurrence::from_token("every:3d"),
+            Some(Ok(Recurrence::EveryNDays(3)))
+        );
+        assert_eq!(
+            Recurrence::from_token("after:2d"),
+            Some(Ok(Recurrence::AfterCompletion(2)))
+        );
+        assert_eq!(
+            Recurrence::from_token("every:month"),
+            Some(Ok(Recurrence::Monthly))
+        );
+        assert!(matches!(
+            Recurrence::from_token("every:someday"),
+            Some(Err(_))
+        ));
+        assert_eq!(Recurrence::from_token("everyday"), None);
+    }
+
+    #[test]
+    fn tokens_round_trip() {
+        for token in &["every:day", "every:mon,thu", "every:month", "every:3d", "after:2d"] {
+            let recurrence = Recurrence::from_token(token).unwrap().unwrap();
+            assert_eq!(&recurrence.token(), token);
+        }
+    }
+
+    #[test]
+    fn next_weekly_due_date() {
+        let rule = Recurrence::Weekly(vec![Weekday::Mon, Weekday::Thu]);
+        // 2021-03-01 is a Monday
+        assert_eq!(
+            rule.next_due_date(Some(date(2021, 3, 1)), date(2021, 3, 1)),
+            date(2021, 3, 4)
+        );
+        assert_eq!(
+            rule.next_due_date(Some(date(2021, 3, 4)), date(2021, 3, 4)),
+            date(2021, 3, 8)
+        );
+    }
+
+    #[test]
+    fn overdue_occurrences_are_skipped() {
+        let rule = Recurrence::Daily;
+        assert_eq!(
+            rule.next_due_date(Some(date(2021, 3, 1)), date(2021, 3, 10)),
+            date(2021, 3, 11)
+        );
+    }
+
+    #[test]
+    fn monthly_due_date_is_clamped() {
+        assert_eq!(
+            Recurrence::Monthly.next_due_date(Some(date(2021, 1, 31)), date(2021, 1, 31)),
+            date(2021, 2, 28)
+        );
+    }
+
+    #[test]
+    fn after_completion() {
+        assert_eq!(
+            Recurrence::AfterCompletion(3).next_due_date(Some(date(2021, 3, 1)), date(2021, 3, 5)),
+            date(2021, 3, 8)
+        );
+    }
+}
@@ -1,12 +1,14 @@
 use colored::Colorize;
 
 use crate::{
+    command::Command,
     editor::Editor,
     errors, parser,
-    todolist::TodoList,
+    todolist::{Resolution, TodoList},
 };
 
 /// dodo's Read-Eval-Print Loop
+#[allow(clippy::upper_case_acronyms)]
 pub struct REPL {
     todo_list: TodoList,
     editor: Editor,
@@ -15,25 +17,98 @@ pub struct REPL {
 impl REPL {
     pub fn new(todo_list: TodoList) -> Result<Self, errors::Error> {
         Ok(Self {
-            editor: Editor::new(&todo_list.config_path),
+            editor: Editor::new(todo_list.config_path.as_deref()),
             todo_list,
         })
     }
 
+    /// Saves the list, asking what to do if it was changed elsewhere since it was loaded.
+    /// Returns whether it was saved
+    fn save(&mut self) -> bool {
+        let result = match self.todo_list.save() {
+            Err(errors::Error::ExternalChanges) => {
+                println!(
+                    "{}: the list was changed elsewhere since it was loaded.",
+                    "warning".yellow()
+                );
+                let resolution = self.ask_resolution();
+                self.todo_list.resolve(resolution)
+            }
+            result => result,
+        };
+
+        match result {
+            Ok(()) => true,
+            Err(err) => {
+                println!("{}: {}", "error".red(), err);
+                false
+            }
+        }
+    }
+
+    /// Asks whether to reload, merge or overwrite the list. Merges when there's no answer
+    fn ask_resolution(&mut self) -> Resolution {
+        loop {
+            let answer = match self.editor.read_line("(r)eload, (m)erge or (o)verwrite? ") {
+                Ok(answer) => answer,
+                Err(_) => return Resolution::Merge,
+            };
+            match answer.trim() {
+                "r" | "reload" => return Resolution::Reload,
+                "m" | "merge" => return Resolution::Merge,
+                "o" | "overwrite" => return Resolution::Overwrite,
+                _ => continue,
+            }
+        }
+    }
+
     /// Starts the loop until an exit signal is given
     pub fn start_loop(&mut self) -> Result<(), errors::Error> {
         loop {
-            match self.editor.read_line(">> ") {
+            match self.editor.read_line(&self.todo_list.config.prompt) {
                 Ok(line) => {
-                    let command = parser::parse(line.as_str());
-                    self.todo_list.evaluate(command);
+                    let commands = parser::parse(&line, &self.todo_list.config);
+                    for command in commands {
+                        match command {
+                            Command::Quit(false) if self.todo_list.is_dirty() => println!(
+                                "{}: there are unsaved changes. Type `save` to save them, or `quit!` to discard them.",
+                                "warning".yellow()
+                            ),
+                            Command::Quit(_) => {
+                                self.editor.save_history(self.todo_list.config_path.as_deref());
+                                return Ok(());
+                            }
+                            Command::Save => {
+                                if self.save() {
+                                    println!("{}: saved the list.", "info".yellow());
+                                }
+                            }
+                            Command::Reload(false) if self.todo_list.is_dirty() => println!(
+                                "{}: there are unsaved changes. Type `reload!` to discard them.",
+                                "warning".yellow()
+                            ),
+                            Command::Reload(_) => match self.todo_list.reload() {
+                                Ok(()) => println!("{}: reloaded the list.", "info".yellow()),
+                                Err(err) => println!("{}: {}", "error".red(), err),
+                            },
+                            command => {
+                                self.todo_list.evaluate(command);
+                                // Storages that only write what changed save every command in its own transaction
+                                let autosave = self.todo_list.config.autosave
+                                    || self.todo_list.storage.is_incremental();
+                                if autosave && self.todo_list.is_dirty() {
+                                    self.save();
+                                }
+                            }
+                        }
+                    }
                 }
                 Err(err) => {
                     // Prints some additional info depending on which error we're getting
                     Editor::show_error(err);
-                    self.editor.save_history(&self.todo_list.config_path);
-                    if let Err(err) = self.todo_list.save_to_file() {
-                        println!("{}: {}", "error".red(), err);
+                    self.editor.save_history(self.todo_list.config_path.as_deref());
+                    if self.todo_list.is_dirty() {
+                        self.save();
                     }
                     return Ok(());
                 }
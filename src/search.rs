@@ -0,0 +1,294 @@
+use colored::Colorize;
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use regex::{Regex, RegexBuilder};
+
+use crate::task::{Task, TaskGroup};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+/// How the search pattern is matched against tasks
+pub enum SearchMode {
+    /// The pattern must appear as is
+    Text,
+    /// The pattern is a regular expression
+    Regex,
+    /// The pattern's characters must appear in order, but not necessarily next to each other
+    Fuzzy,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+/// A search across every group of the todo list
+pub struct SearchQuery {
+    pub pattern: String,
+    pub mode: SearchMode,
+    pub ignore_case: bool,
+}
+
+/// A task that matched a search
+pub struct SearchMatch<'a> {
+    /// Number of the group the task belongs to
+    pub group_no: usize,
+    /// Number of the task within its group
+    pub task_no: usize,
+    pub group: &'a TaskGroup,
+    pub task: &'a Task,
+    /// Which characters of the description matched the pattern
+    highlighted: Vec<bool>,
+    /// The task's tags that matched the pattern
+    matched_tags: Vec<&'a str>,
+    score: i64,
+}
+
+enum Matcher {
+    Regex(Regex),
+    Fuzzy(Box<SkimMatcherV2>),
+}
+
+impl Matcher {
+    /// Returns the score of the match along with which characters of `text` matched, if any did
+    fn find(&self, text: &str, pattern: &str) -> Option<(i64, Vec<bool>)> {
+        let mut highlighted = vec![false; text.chars().count()];
+
+        match self {
+            Matcher::Regex(regex) => {
+                let spans: Vec<_> = regex
+                    .find_iter(text)
+                    .filter(|span| !span.as_str().is_empty())
+                    .map(|span| span.range())
+                    .collect();
+                if spans.is_empty() {
+                    return None;
+                }
+                for (char_idx, (byte_idx, _)) in text.char_indices().enumerate() {
+                    highlighted[char_idx] = spans.iter().any(|span| span.contains(&byte_idx));
+                }
+                Some((0, highlighted))
+            }
+            Matcher::Fuzzy(fuzzy) => {
+                let (score, indices) = fuzzy.fuzzy_indices(text, pattern)?;
+                for char_idx in indices {
+                    highlighted[char_idx] = true;
+                }
+                Some((score, highlighted))
+            }
+        }
+    }
+}
+
+impl SearchQuery {
+    /// Checks that the pattern is valid, returning a description of the problem if it isn't
+    pub fn validate(&self) -> Result<(), String> {
+        self.matcher().map(|_| ())
+    }
+
+    fn matcher(&self) -> Result<Matcher, String> {
+        let pattern = match self.mode {
+            SearchMode::Text => regex::escape(&self.pattern),
+            SearchMode::Regex => self.pattern.clone(),
+            SearchMode::Fuzzy => {
+                let matcher = SkimMatcherV2::default();
+                return Ok(Matcher::Fuzzy(Box::new(match self.ignore_case {
+                    true => matcher.ignore_case(),
+                    false => matcher.smart_case(),
+                })));
+            }
+        };
+
+        RegexBuilder::new(&pattern)
+            .case_insensitive(self.ignore_case)
+            .build()
+            .map(Matcher::Regex)
+            .map_err(|err| err.to_string())
+    }
+
+    /// Searches the descriptions and tags of every task of the given groups.
+    /// Fuzzy matches are sorted from best to worst, other matches keep the list's order.
+    pub fn run<'a>(&self, task_groups: &'a [TaskGroup]) -> Result<Vec<SearchMatch<'a>>, String> {
+        let matcher = self.matcher()?;
+        let mut matches = vec![];
+
+        for (group_idx, group) in task_groups.iter().enumerate() {
+            for (task_idx, task) in group.tasks.iter().enumerate() {
+                let description_match = matcher.find(&task.description, &self.pattern);
+                let tag_matches: Vec<(i64, &str)> = task
+                    .tags
+                    .iter()
+                    .filter_map(|tag| Some((matcher.find(tag, &self.pattern)?.0, tag.as_str())))
+                    .collect();
+
+                if description_match.is_none() && tag_matches.is_empty() {
+                    continue;
+                }
+
+                let (description_score, highlighted) = description_match
+                    .unwrap_or_else(|| (i64::MIN, vec![false; task.description.chars().count()]));
+                let score = tag_matches
+                    .iter()
+                    .map(|(score, _)| *score)
+                    .fold(description_score, i64::max);
+                let matched_tags = tag_matches.into_iter().map(|(_, tag)| tag).collect();
+
+                matches.push(SearchMatch {
+                    group_no: group_idx + 1,
+                    task_no: task_idx + 1,
+                    group,
+                    task,
+                    highlighted,
+                    matched_tags,
+                    score,
+                });
+            }
+        }
+
+        if self.mode == SearchMode::Fuzzy {
+            // The sort is stable, so equally good matches stay in the list's order
+            matches.sort_by_key(|found| -found.score);
+        }
+
+        Ok(matches)
+    }
+}
+
+impl SearchMatch<'_> {
+    /// The task's description, with the matched characters passed through `highlight`
+    fn highlighted_description(&self, highlight: &dyn Fn(&str) -> String) -> String {
+        self.task
+            .description
+            .chars()
+            .zip(&self.highlighted)
+            .map(|(ch, &highlighted)| match highlighted {
+                true => highlight(&ch.to_string()),
+                false => ch.to_string(),
+            })
+            .collect()
+    }
+
+    /// The match as `find` shows it, with the matched parts passed through `highlight`
+    fn format(&self, highlight: &dyn Fn(&str) -> String) -> String {
+        let mut line = format!(
+            "{}.{} [{}] - {}",
+            self.group_no,
+            self.task_no,
+            if self.task.is_done { "x" } else { " " },
+            self.highlighted_description(highlight),
+        );
+
+        for tag in &self.task.tags {
+            let tag = format!("+{}", tag);
+            match self.matched_tags.contains(&&tag[1..]) {
+                true => line += &format!(" {}", highlight(&tag)),
+                false => line += &format!(" {}", tag),
+            }
+        }
+
+        line + &format!(" ({})", self.group.name)
+    }
+}
+
+impl std::fmt::Display for SearchMatch<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let line = self.format(&|text| text.yellow().bold().to_string());
+        write!(f, "{}", line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_groups() -> Vec<TaskGroup> {
+        let task = |description: &str| Task {
+            description: description.into(),
+            ..Default::default()
+        };
+
+        vec![
+            TaskGroup {
+                name: "Work".into(),
+                tasks: vec![task("Deploy review"), task("Write the report")],
+            },
+            TaskGroup {
+                name: "Chores".into(),
+                tasks: vec![
+                    task("Review the budget"),
+                    task("Do the dishes"),
+                    Task {
+                        tags: vec!["review".into()],
+                        ..task("Plan the week")
+                    },
+                ],
+            },
+        ]
+    }
+
+    fn found_refs(query: &SearchQuery) -> Vec<(usize, usize)> {
+        let groups = sample_groups();
+        query
+            .run(&groups)
+            .unwrap()
+            .iter()
+            .map(|found| (found.group_no, found.task_no))
+            .collect()
+    }
+
+    #[test]
+    fn text_search() {
+        let mut query = SearchQuery {
+            pattern: "review".into(),
+            mode: SearchMode::Text,
+            ignore_case: false,
+        };
+        // "Plan the week" is found through its tag
+        assert_eq!(found_refs(&query), vec![(1, 1), (2, 3)]);
+
+        query.ignore_case = true;
+        assert_eq!(found_refs(&query), vec![(1, 1), (2, 1), (2, 3)]);
+    }
+
+    #[test]
+    fn regex_search() {
+        let query = SearchQuery {
+            pattern: "^(Do|Write) ".into(),
+            mode: SearchMode::Regex,
+            ignore_case: false,
+        };
+        assert_eq!(found_refs(&query), vec![(1, 2), (2, 2)]);
+
+        let bad_query = SearchQuery {
+            pattern: "(".into(),
+            ..query
+        };
+        assert!(bad_query.validate().is_err());
+    }
+
+    #[test]
+    fn fuzzy_search() {
+        let query = SearchQuery {
+            pattern: "dshs".into(),
+            mode: SearchMode::Fuzzy,
+            ignore_case: true,
+        };
+        assert_eq!(found_refs(&query), vec![(2, 2)]);
+    }
+
+    #[test]
+    fn highlighting() {
+        let groups = sample_groups();
+        let query = SearchQuery {
+            pattern: "the".into(),
+            mode: SearchMode::Text,
+            ignore_case: false,
+        };
+        let matches = query.run(&groups).unwrap();
+
+        assert_eq!(matches.len(), 4);
+        assert_eq!(matches[0].highlighted, {
+            let mut expected = vec![false; "Write the report".len()];
+            expected[6..9].iter_mut().for_each(|ch| *ch = true);
+            expected
+        });
+        assert_eq!(
+            matches[0].format(&|text| format!("[{}]", text)),
+            "1.2 [ ] - Write [t][h][e] report (Work)"
+        );
+    }
+}
@@ -0,0 +1,279 @@
+use std::fmt;
+
+use chrono::{Datelike, Duration, NaiveDate};
+
+use crate::display;
+use crate::todolist::TodoList;
+
+/// How many days are shown in the tasks completed per day
+const DAYS_SHOWN: i64 = 7;
+/// How many weeks are shown in the tasks completed per week
+const WEEKS_SHOWN: i64 = 4;
+/// How many of the oldest open tasks are shown
+const OLDEST_SHOWN: usize = 5;
+
+/// A group's progress, as shown by `stats`
+pub struct GroupStatistics {
+    pub name: String,
+    pub done: usize,
+    pub total: usize,
+    pub completion_rate: usize,
+}
+
+/// A task that is still pending, as shown by `stats`
+pub struct OpenTask {
+    /// The task's reference, in the `G.T` format
+    pub reference: String,
+    pub description: String,
+    /// For how many days the task has been open
+    pub age: i64,
+}
+
+/// Statistics about a todo list
+pub struct Statistics {
+    pub done: usize,
+    pub total: usize,
+    pub archived: usize,
+    pub groups: Vec<GroupStatistics>,
+    /// How many tasks were completed on each of the last days, oldest first
+    pub completed_per_day: Vec<(NaiveDate, usize)>,
+    /// How many tasks were completed on each of the last weeks, by the Monday they start on
+    pub completed_per_week: Vec<(NaiveDate, usize)>,
+    /// The tasks that have been pending for the longest, oldest first
+    pub oldest_open: Vec<OpenTask>,
+    /// How dates are shown, in strftime format
+    pub date_format: String,
+}
+
+impl Statistics {
+    /// Computes the statistics of the given list, as of `today`
+    pub fn compute(list: &TodoList, today: NaiveDate) -> Self {
+        let tasks = list.task_groups.iter().flat_map(|group| group.tasks.iter());
+
+        let completion_dates: Vec<NaiveDate> = tasks
+            .clone()
+            .filter(|task| task.is_done)
+            .filter_map(|task| task.completed_at)
+            .chain(list.archive.entries.iter().map(|entry| entry.completed_at))
+            .map(|time| time.naive_local().date())
+            .collect();
+        let completed_between = |start: NaiveDate, end: NaiveDate| {
+            completion_dates
+                .iter()
+                .filter(|&&date| start <= date && date < end)
+                .count()
+        };
+
+        let completed_per_day = (0..DAYS_SHOWN)
+            .rev()
+            .map(|days_ago| today - Duration::days(days_ago))
+            .map(|day| (day, completed_between(day, day + Duration::days(1))))
+            .collect();
+
+        let this_week = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+        let completed_per_week = (0..WEEKS_SHOWN)
+            .rev()
+            .map(|weeks_ago| this_week - Duration::weeks(weeks_ago))
+            .map(|week| (week, completed_between(week, week + Duration::weeks(1))))
+            .collect();
+
+        let mut oldest_open: Vec<OpenTask> = list
+            .task_groups
+            .iter()
+            .enumerate()
+            .flat_map(|(group_idx, group)| {
+                group
+                    .tasks
+                    .iter()
+                    .enumerate()
+                    .map(move |(task_idx, task)| (group_idx, task_idx, task))
+            })
+            .filter(|(_, _, task)| !task.is_done)
+            .filter_map(|(group_idx, task_idx, task)| {
+                let created_on = task.created_at?.naive_local().date();
+                Some(OpenTask {
+                    reference: format!("{}.{}", group_idx + 1, task_idx + 1),
+                    description: task.description.clone(),
+                    age: (today - created_on).num_days(),
+                })
+            })
+            .collect();
+        // The sort is stable, so tasks of the same age stay in the list's order
+        oldest_open.sort_by_key(|task| -task.age);
+        oldest_open.truncate(OLDEST_SHOWN);
+
+        Self {
+            done: tasks.clone().filter(|task| task.is_done).count(),
+            total: tasks.count(),
+            archived: list.archive.entries.len(),
+            groups: list
+                .task_groups
+                .iter()
+                .map(|group| {
+                    let (done, total) = group.progress();
+                    GroupStatistics {
+                        name: group.name.clone(),
+                        done,
+                        total,
+                        completion_rate: group.completion_rate(),
+                    }
+                })
+                .collect(),
+            completed_per_day,
+            completed_per_week,
+            oldest_open,
+            date_format: list.config.date_format.clone(),
+        }
+    }
+
+    /// The percentage of tasks that are done, rounded to the nearest integer
+    pub fn completion_rate(&self) -> usize {
+        match self.total {
+            0 => 0,
+            total => (self.done * 200 + total) / (total * 2),
+        }
+    }
+}
+
+impl fmt::Display for Statistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Tasks: {} ({} done, {} pending, {}% complete), {} archived",
+            self.total,
+            self.done,
+            self.total - self.done,
+            self.completion_rate(),
+            self.archived
+        )?;
+
+        writeln!(f, "\nGroups:")?;
+        for (i, group) in self.groups.iter().enumerate() {
+            writeln!(
+                f,
+                "\t{}. {}: {}/{} done ({}%)",
+                i + 1,
+                group.name,
+                group.done,
+                group.total,
+                group.completion_rate
+            )?;
+        }
+
+        writeln!(f, "\nCompleted per day:")?;
+        for (day, completed) in &self.completed_per_day {
+            writeln!(
+                f,
+                "\t{} {} {:>3} {}",
+                day.format("%a"),
+                display::format_date(*day, &self.date_format),
+                completed,
+                "█".repeat(*completed)
+            )?;
+        }
+
+        writeln!(f, "\nCompleted per week:")?;
+        for (week, completed) in &self.completed_per_week {
+            writeln!(
+                f,
+                "\tweek of {} {:>3} {}",
+                display::format_date(*week, &self.date_format),
+                completed,
+                "█".repeat(*completed)
+            )?;
+        }
+
+        writeln!(f, "\nOldest open tasks:")?;
+        if self.oldest_open.is_empty() {
+            writeln!(f, "\tNone")?;
+        }
+        for task in &self.oldest_open {
+            writeln!(
+                f,
+                "\t{} {} (open for {} days)",
+                task.reference, task.description, task.age
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use chrono::{Local, TimeZone};
+
+    use super::*;
+    use crate::errors;
+    use crate::task::{Task, TaskGroup};
+
+    fn at(year: i32, month: u32, day: u32) -> Option<chrono::DateTime<Local>> {
+        Local.with_ymd_and_hms(year, month, day, 12, 0, 0).single()
+    }
+
+    #[test]
+    fn statistics() -> Result<(), errors::Error> {
+        let task = |description: &str, created_at, completed_at: Option<_>| Task {
+            description: description.into(),
+            is_done: completed_at.is_some(),
+            created_at,
+            completed_at,
+            ..Default::default()
+        };
+
+        let mut list = TodoList::try_from(vec![
+            TaskGroup {
+                name: "Release".into(),
+                tasks: vec![
+                    task("Tag the release", at(2021, 2, 1), at(2021, 3, 1)),
+                    task("Write the changelog", at(2021, 2, 20), None),
+                    task("Announce it", at(2021, 2, 10), None),
+                ],
+            },
+            TaskGroup {
+                name: "Chores".into(),
+                tasks: vec![
+                    task("Clean the house", None, at(2021, 3, 3)),
+                    task("Do the dishes", None, at(2021, 2, 10)),
+                ],
+            },
+        ])?;
+
+        list.config.date_format = "%d/%m".into();
+
+        // 2021-03-03 is a Wednesday
+        let stats = Statistics::compute(&list, NaiveDate::from_ymd_opt(2021, 3, 3).unwrap());
+
+        assert_eq!(
+            (stats.done, stats.total, stats.completion_rate()),
+            (3, 5, 60)
+        );
+        assert_eq!(stats.groups[0].completion_rate, 33);
+        assert_eq!(stats.groups[1].completion_rate, 100);
+
+        let per_day: Vec<usize> = stats.completed_per_day.iter().map(|(_, n)| *n).collect();
+        assert_eq!(per_day, vec![0, 0, 0, 0, 1, 0, 1]);
+
+        let per_week: Vec<usize> = stats.completed_per_week.iter().map(|(_, n)| *n).collect();
+        assert_eq!(per_week, vec![1, 0, 0, 2]);
+        assert_eq!(
+            stats.completed_per_week[3].0,
+            NaiveDate::from_ymd_opt(2021, 3, 1).unwrap()
+        );
+
+        let oldest: Vec<(&str, i64)> = stats
+            .oldest_open
+            .iter()
+            .map(|task| (task.reference.as_str(), task.age))
+            .collect();
+        assert_eq!(oldest, vec![("1.3", 21), ("1.2", 11)]);
+
+        let shown = stats.to_string();
+        assert!(shown.contains("\tWed 03/03   1 █\n"));
+        assert!(shown.contains("\tweek of 01/03   2 ██\n"));
+
+        Ok(())
+    }
+}
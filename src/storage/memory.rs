@@ -0,0 +1,26 @@
+use super::{Storage, StoredList};
+use crate::errors::Error;
+
+#[derive(Debug, Default)]
+/// Keeps the list in memory only, e.g. for tests
+pub struct MemoryStorage {
+    list: StoredList,
+}
+
+impl MemoryStorage {
+    /// Creates a storage that already holds the given list
+    pub fn with_list(list: StoredList) -> Self {
+        Self { list }
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn load(&mut self) -> Result<StoredList, Error> {
+        Ok(self.list.clone())
+    }
+
+    fn save(&mut self, list: &StoredList) -> Result<(), Error> {
+        self.list = list.clone();
+        Ok(())
+    }
+}
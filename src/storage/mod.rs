@@ -0,0 +1,250 @@
+use std::{cell::RefCell, collections::BTreeMap, fmt, path::Path, rc::Rc, str::FromStr};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::archive::Archive;
+use crate::config::Config;
+use crate::errors::Error;
+use crate::task::TaskGroup;
+
+mod memory;
+mod ron_files;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+pub use memory::MemoryStorage;
+pub use ron_files::{parse_ron_files, ron_file_names, to_ron, RonStorage};
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStorage;
+
+/// The names of every backend, as given to `config set storage`
+pub const BACKEND_NAMES: &[&str] = &["ron", "memory", "sqlite"];
+
+#[cfg(not(feature = "sqlite"))]
+static SQLITE_UNAVAILABLE: &str =
+    "dodo was built without SQLite support. Rebuild it with `--features sqlite`";
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+/// Where todo lists are stored
+pub enum Backend {
+    /// RON files in the config folder
+    #[default]
+    Ron,
+    /// Nowhere: changes are lost when dodo exits. Handy to try things out
+    Memory,
+    /// An SQLite database in the config folder. Only available when built with the `sqlite` feature
+    Sqlite,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(backend: &str) -> Result<Self, Self::Err> {
+        match backend {
+            "ron" => Ok(Backend::Ron),
+            "memory" => Ok(Backend::Memory),
+            "sqlite" => Ok(Backend::Sqlite),
+            _ => Err(format!(
+                "`{}` is not a known storage backend. Expected one of: {}",
+                backend,
+                BACKEND_NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+impl Backend {
+    /// Checks that dodo was built with support for this backend
+    pub fn check_available(self) -> Result<(), String> {
+        #[cfg(not(feature = "sqlite"))]
+        if self == Backend::Sqlite {
+            return Err(SQLITE_UNAVAILABLE.into());
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Backend::Ron => write!(f, "ron"),
+            Backend::Memory => write!(f, "memory"),
+            Backend::Sqlite => write!(f, "sqlite"),
+        }
+    }
+}
+
+/// Backends are written by name in the config file, e.g. `storage: "ron"`
+impl Serialize for Backend {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Backend {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+/// Everything about a todo list that's stored, apart from the configuration
+pub struct StoredList {
+    pub task_groups: Vec<TaskGroup>,
+    pub archive: Archive,
+    pub saved_queries: BTreeMap<String, String>,
+}
+
+/// A place todo lists are loaded from and saved to
+pub trait Storage {
+    /// Loads the stored list. Storages that hold nothing yet return an empty list
+    fn load(&mut self) -> Result<StoredList, Error>;
+
+    /// Replaces the stored list with the given one. Storages that can be changed by other programs
+    /// fail with `Error::ExternalChanges` when they were changed since they were last loaded or saved
+    fn save(&mut self, list: &StoredList) -> Result<(), Error>;
+
+    /// Saves a list that was changed since it was loaded or last saved as `before`.
+    /// Storages that can update only what changed do so, while others save the whole list
+    fn save_changes(&mut self, before: &StoredList, after: &StoredList) -> Result<(), Error> {
+        let _ = before;
+        self.save(after)
+    }
+
+    /// Whether saving is cheap enough to be done after every command, instead of when dodo exits
+    fn is_incremental(&self) -> bool {
+        false
+    }
+}
+
+/// Opens the storage chosen in the given config, with `config_path` being the config folder
+pub fn open(config: &Config, config_path: &Path) -> Result<SharedStorage, Error> {
+    let storage = match config.storage {
+        Backend::Ron => SharedStorage::new(RonStorage::new(config_path.into(), config.pretty_ron)),
+        Backend::Memory => SharedStorage::new(MemoryStorage::default()),
+        #[cfg(feature = "sqlite")]
+        Backend::Sqlite => SharedStorage::new(SqliteStorage::open(config_path)?),
+        #[cfg(not(feature = "sqlite"))]
+        Backend::Sqlite => return Err(Error::StorageError(SQLITE_UNAVAILABLE.into())),
+    };
+    Ok(storage)
+}
+
+#[derive(Clone)]
+/// A storage shared by a todo list and its copies, which remembers what was last loaded or saved
+/// so that only changes need to be saved
+pub struct SharedStorage {
+    storage: Rc<RefCell<dyn Storage>>,
+    last_saved: Rc<RefCell<Option<StoredList>>>,
+}
+
+impl SharedStorage {
+    pub fn new(storage: impl Storage + 'static) -> Self {
+        Self {
+            storage: Rc::new(RefCell::new(storage)),
+            last_saved: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    pub fn load(&self) -> Result<StoredList, Error> {
+        let list = self.storage.borrow_mut().load()?;
+        *self.last_saved.borrow_mut() = Some(list.clone());
+        Ok(list)
+    }
+
+    pub fn save(&self, list: &StoredList) -> Result<(), Error> {
+        let mut last_saved = self.last_saved.borrow_mut();
+        match last_saved.as_ref() {
+            Some(before) => self.storage.borrow_mut().save_changes(before, list)?,
+            None => self.storage.borrow_mut().save(list)?,
+        }
+
+        *last_saved = Some(list.clone());
+        Ok(())
+    }
+
+    /// The list as it was last loaded or saved
+    pub fn last_saved(&self) -> Option<StoredList> {
+        self.last_saved.borrow().clone()
+    }
+
+    pub fn is_incremental(&self) -> bool {
+        self.storage.borrow().is_incremental()
+    }
+}
+
+impl Default for SharedStorage {
+    fn default() -> Self {
+        Self::new(MemoryStorage::default())
+    }
+}
+
+impl fmt::Debug for SharedStorage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SharedStorage")
+    }
+}
+
+/// Lists are compared by their contents, no matter where they're stored
+impl PartialEq for SharedStorage {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for SharedStorage {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::Task;
+
+    /// Records the changes it's given to save, as the number of tasks before and after them
+    #[derive(Default)]
+    struct RecordingStorage {
+        saved_changes: Rc<RefCell<Vec<(usize, usize)>>>,
+    }
+
+    impl Storage for RecordingStorage {
+        fn load(&mut self) -> Result<StoredList, Error> {
+            Ok(StoredList {
+                task_groups: vec![TaskGroup {
+                    name: "Work".into(),
+                    tasks: vec![],
+                }],
+                ..Default::default()
+            })
+        }
+
+        fn save(&mut self, _: &StoredList) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn save_changes(&mut self, before: &StoredList, after: &StoredList) -> Result<(), Error> {
+            let task_count = |list: &StoredList| list.task_groups[0].tasks.len();
+            self.saved_changes
+                .borrow_mut()
+                .push((task_count(before), task_count(after)));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn saving_changes() -> Result<(), Error> {
+        let recording = RecordingStorage::default();
+        let saved_changes = recording.saved_changes.clone();
+        let storage = SharedStorage::new(recording);
+
+        let mut list = storage.load()?;
+        list.task_groups[0]
+            .tasks
+            .push(Task::new("Write the report".into()));
+        storage.save(&list)?;
+        storage.save(&list)?;
+
+        assert_eq!(*saved_changes.borrow(), vec![(0, 1), (1, 1)]);
+        Ok(())
+    }
+}
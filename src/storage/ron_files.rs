@@ -0,0 +1,261 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    fs::{self, File, OpenOptions},
+    hash::{Hash, Hasher},
+    path::PathBuf,
+};
+
+use colored::Colorize;
+use fs2::FileExt;
+use ron::ser::PrettyConfig;
+use serde::Serialize;
+
+use super::{Storage, StoredList};
+use crate::archive::Archive;
+use crate::errors::Error;
+
+static SAVE_FILE_NAME: &str = "dodo.ron";
+static ARCHIVE_FILE_NAME: &str = "archive.ron";
+static QUERIES_FILE_NAME: &str = "queries.ron";
+static LOCK_FILE_NAME: &str = "dodo.lock";
+
+/// The files the list is kept in: the groups, the archive and the saved queries
+pub fn ron_file_names() -> [&'static str; 3] {
+    [SAVE_FILE_NAME, ARCHIVE_FILE_NAME, QUERIES_FILE_NAME]
+}
+
+/// Reads a list from the contents of its files, in the order of `ron_file_names`.
+/// Files that don't exist, given as None, hold nothing yet
+pub fn parse_ron_files(contents: &[Option<String>]) -> Result<StoredList, Error> {
+    let task_groups = match &contents[0] {
+        Some(file_contents) => ron::de::from_str(file_contents)?,
+        None => vec![],
+    };
+
+    let archive = match &contents[1] {
+        Some(file_contents) => ron::de::from_str(file_contents)?,
+        None => Archive::default(),
+    };
+
+    let saved_queries = match &contents[2] {
+        Some(file_contents) => ron::de::from_str(file_contents)?,
+        None => BTreeMap::new(),
+    };
+
+    Ok(StoredList {
+        task_groups,
+        archive,
+        saved_queries,
+    })
+}
+
+/// How deep each file is pretty-printed, so that every task, archived task and query
+/// ends up on a line of its own
+const GROUPS_DEPTH: usize = 3;
+const ARCHIVE_DEPTH: usize = 1;
+const QUERIES_DEPTH: usize = 1;
+
+/// Serializes the given value in RON, either on a single line or pretty-printed up to the given
+/// depth, past which values are kept on one line. Fields are always written in the same order,
+/// so that files only change where the list did
+pub fn to_ron<T: Serialize>(value: &T, pretty: bool, depth: usize) -> Result<String, Error> {
+    if !pretty {
+        return Ok(ron::ser::to_string(value)?);
+    }
+
+    let config = PrettyConfig::new()
+        .with_depth_limit(depth)
+        .with_indentor("  ".into());
+    Ok(ron::ser::to_string_pretty(value, config)? + "\n")
+}
+
+#[derive(Debug)]
+/// Keeps the list in RON files: `dodo.ron` for the groups, `archive.ron` for the archive
+/// and `queries.ron` for the saved queries
+pub struct RonStorage {
+    folder: PathBuf,
+    /// Whether the files are pretty-printed. Both compact and pretty files can be loaded
+    pretty: bool,
+    /// The fingerprint of the files as they were last loaded or saved
+    fingerprint: Option<u64>,
+}
+
+/// Hashes the contents of the files, to tell whether something else changed them
+fn fingerprint(contents: &[Option<String>]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl RonStorage {
+    pub fn new(folder: PathBuf, pretty: bool) -> Self {
+        Self {
+            folder,
+            pretty,
+            fingerprint: None,
+        }
+    }
+
+    /// Takes an advisory lock on the files until the returned file is closed,
+    /// so that other dodos don't read them while they're being written, or write them at the same time
+    fn lock(&self, exclusive: bool) -> Result<File, Error> {
+        let lock_file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.folder.join(LOCK_FILE_NAME))?;
+        match exclusive {
+            true => lock_file.lock_exclusive()?,
+            false => lock_file.lock_shared()?,
+        }
+        Ok(lock_file)
+    }
+
+    /// Reads the contents of every file, or None for the ones that don't exist
+    fn read(&self) -> Result<Vec<Option<String>>, Error> {
+        let mut contents = vec![];
+        for file_name in &ron_file_names() {
+            let path = self.folder.join(file_name);
+            contents.push(match path.exists() {
+                true => Some(fs::read_to_string(path)?),
+                false => None,
+            });
+        }
+        Ok(contents)
+    }
+}
+
+impl Storage for RonStorage {
+    fn load(&mut self) -> Result<StoredList, Error> {
+        let contents = {
+            let _lock = self.lock(false)?;
+            self.read()?
+        };
+
+        if contents[0].is_none() {
+            println!(
+                "{}: could not find a `dodo.ron` in `{:#?}`. \nCreating a new save file.",
+                "warning".yellow(),
+                self.folder.join(SAVE_FILE_NAME)
+            );
+        }
+
+        let list = parse_ron_files(&contents)?;
+        self.fingerprint = Some(fingerprint(&contents));
+        Ok(list)
+    }
+
+    /// Fails with `Error::ExternalChanges` if the files were changed by something else
+    /// since they were last loaded or saved
+    fn save(&mut self, list: &StoredList) -> Result<(), Error> {
+        let contents = vec![
+            Some(to_ron(&list.task_groups, self.pretty, GROUPS_DEPTH)?),
+            Some(to_ron(&list.archive, self.pretty, ARCHIVE_DEPTH)?),
+            Some(to_ron(&list.saved_queries, self.pretty, QUERIES_DEPTH)?),
+        ];
+
+        let _lock = self.lock(true)?;
+        if let Some(loaded) = self.fingerprint {
+            if fingerprint(&self.read()?) != loaded {
+                return Err(Error::ExternalChanges);
+            }
+        }
+
+        for (file_name, file_contents) in ron_file_names().iter().zip(&contents) {
+            fs::write(
+                self.folder.join(file_name),
+                file_contents.as_deref().unwrap_or_default(),
+            )?;
+        }
+
+        self.fingerprint = Some(fingerprint(&contents));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::{Task, TaskGroup};
+
+    #[test]
+    fn round_trip() -> Result<(), Error> {
+        let folder = std::env::temp_dir().join(format!("dodo-ron-storage-{}", std::process::id()));
+        fs::create_dir_all(&folder)?;
+        let mut storage = RonStorage::new(folder.clone(), true);
+
+        let mut list = StoredList::default();
+        list.task_groups.push(TaskGroup {
+            name: "Work".into(),
+            tasks: vec![Task::new("Write the report".into())],
+        });
+        list.saved_queries
+            .insert("urgent".into(), "due:today".into());
+
+        storage.save(&list)?;
+        let loaded = storage.load();
+        fs::remove_dir_all(&folder)?;
+        assert_eq!(loaded?, list);
+
+        Ok(())
+    }
+
+    #[test]
+    fn pretty_printing() -> Result<(), Error> {
+        let task = |description: &str| Task {
+            description: description.into(),
+            ..Default::default()
+        };
+        let task_groups = vec![TaskGroup {
+            name: "Work".into(),
+            tasks: vec![task("Write the report"), task("Answer emails")],
+        }];
+
+        let pretty = to_ron(&task_groups, true, GROUPS_DEPTH)?;
+        assert_eq!(
+            pretty,
+            concat!(
+                "[\n",
+                "  (\n",
+                "    name: \"Work\",\n",
+                "    tasks: [\n",
+                "      (description:\"Write the report\",is_done:false),\n",
+                "      (description:\"Answer emails\",is_done:false),\n",
+                "    ],\n",
+                "  ),\n",
+                "]\n",
+            )
+        );
+
+        // Both layouts read the same
+        let compact = to_ron(&task_groups, false, GROUPS_DEPTH)?;
+        assert!(!compact.contains('\n'));
+        for contents in &[pretty, compact] {
+            assert_eq!(ron::de::from_str::<Vec<TaskGroup>>(contents)?, task_groups);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn detecting_external_changes() -> Result<(), Error> {
+        let folder = std::env::temp_dir().join(format!("dodo-ron-changes-{}", std::process::id()));
+        fs::create_dir_all(&folder)?;
+        let mut ours = RonStorage::new(folder.clone(), true);
+        let mut theirs = RonStorage::new(folder.clone(), false);
+
+        let mut list = ours.load()?;
+        theirs.load()?;
+        list.saved_queries
+            .insert("urgent".into(), "due:today".into());
+        theirs.save(&list)?;
+
+        let result = ours.save(&StoredList::default());
+        let reloaded = ours.load();
+        fs::remove_dir_all(&folder)?;
+        assert!(matches!(result, Err(Error::ExternalChanges)));
+        assert_eq!(reloaded?, list);
+
+        Ok(())
+    }
+}
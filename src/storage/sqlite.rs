@@ -0,0 +1,585 @@
+use std::{collections::BTreeMap, path::Path, str::FromStr};
+
+use chrono::{DateTime, Local, NaiveDate};
+use colored::Colorize;
+use rusqlite::{params, Connection, Row, ToSql, Transaction, TransactionBehavior, NO_PARAMS};
+
+use super::{RonStorage, Storage, StoredList};
+use crate::archive::{Archive, ArchivedTask};
+use crate::errors::Error;
+use crate::recurrence::Recurrence;
+use crate::task::{Task, TaskGroup};
+
+static DATABASE_FILE_NAME: &str = "dodo.db";
+static RON_FILE_NAME: &str = "dodo.ron";
+
+/// Bumped whenever the schema changes, with a migration added to `MIGRATIONS`
+const SCHEMA_VERSION: i64 = 1;
+
+/// The statements bringing the schema from each version to the next, starting from an empty database
+const MIGRATIONS: &[&str] = &[
+    // Version 1. Tasks of groups and archived tasks share the task columns
+    "CREATE TABLE groups (
+        position INTEGER PRIMARY KEY,
+        name TEXT NOT NULL
+    );
+    CREATE TABLE tasks (
+        group_position INTEGER NOT NULL,
+        position INTEGER NOT NULL,
+        description TEXT NOT NULL,
+        is_done INTEGER NOT NULL,
+        created_at TEXT,
+        completed_at TEXT,
+        modified_at TEXT,
+        due_date TEXT,
+        recurrence TEXT,
+        priority TEXT,
+        tags TEXT NOT NULL,
+        uid TEXT,
+        PRIMARY KEY (group_position, position)
+    );
+    CREATE TABLE archive (
+        position INTEGER PRIMARY KEY,
+        group_name TEXT NOT NULL,
+        archived_completed_at TEXT NOT NULL,
+        description TEXT NOT NULL,
+        is_done INTEGER NOT NULL,
+        created_at TEXT,
+        completed_at TEXT,
+        modified_at TEXT,
+        due_date TEXT,
+        recurrence TEXT,
+        priority TEXT,
+        tags TEXT NOT NULL,
+        uid TEXT
+    );
+    CREATE TABLE queries (
+        name TEXT PRIMARY KEY,
+        query TEXT NOT NULL
+    );",
+];
+
+/// The columns every task is stored in, in order
+const TASK_COLUMNS: &str = "description, is_done, created_at, completed_at, modified_at, \
+                            due_date, recurrence, priority, tags, uid";
+
+/// Keeps the list in `dodo.db`, an SQLite database in the config folder.
+/// Only the parts of the list that changed are written when it's saved
+pub struct SqliteStorage {
+    connection: Connection,
+    /// The data version of the database as it was last loaded or saved, which changes whenever
+    /// another connection writes to it
+    data_version: Option<i64>,
+}
+
+fn data_version(connection: &Connection) -> Result<i64, Error> {
+    Ok(connection.query_row("PRAGMA data_version", NO_PARAMS, |row| row.get(0))?)
+}
+
+fn invalid_column(column: &str, value: &str) -> Error {
+    Error::StorageError(format!("`{}` is not a valid `{}`", value, column))
+}
+
+fn parse_time(column: &str, value: Option<String>) -> Result<Option<DateTime<Local>>, Error> {
+    value
+        .map(|value| {
+            DateTime::parse_from_rfc3339(&value)
+                .map(|time| time.with_timezone(&Local))
+                .map_err(|_| invalid_column(column, &value))
+        })
+        .transpose()
+}
+
+/// Reads the task stored in the task columns of a row, starting from the given column
+fn task_from_row(row: &Row, first: usize) -> Result<Task, Error> {
+    let column = |offset: usize| first + offset;
+
+    let due_date = row
+        .get::<_, Option<String>>(column(5))?
+        .map(|value| {
+            NaiveDate::parse_from_str(&value, "%Y-%m-%d")
+                .map_err(|_| invalid_column("due_date", &value))
+        })
+        .transpose()?;
+    let recurrence = row
+        .get::<_, Option<String>>(column(6))?
+        .map(|value| {
+            Recurrence::from_token(&value)
+                .and_then(Result::ok)
+                .ok_or_else(|| invalid_column("recurrence", &value))
+        })
+        .transpose()?;
+    let priority = row
+        .get::<_, Option<String>>(column(7))?
+        .map(|value| FromStr::from_str(&value).map_err(|_| invalid_column("priority", &value)))
+        .transpose()?;
+    let tags: String = row.get(column(8))?;
+
+    Ok(Task {
+        description: row.get(column(0))?,
+        is_done: row.get(column(1))?,
+        created_at: parse_time("created_at", row.get(column(2))?)?,
+        completed_at: parse_time("completed_at", row.get(column(3))?)?,
+        modified_at: parse_time("modified_at", row.get(column(4))?)?,
+        due_date,
+        recurrence,
+        priority,
+        tags: tags.split_whitespace().map(String::from).collect(),
+        uid: row.get(column(9))?,
+    })
+}
+
+/// The values of the task columns, in the order of `TASK_COLUMNS`
+struct TaskValues {
+    description: String,
+    is_done: bool,
+    created_at: Option<String>,
+    completed_at: Option<String>,
+    modified_at: Option<String>,
+    due_date: Option<String>,
+    recurrence: Option<String>,
+    priority: Option<String>,
+    tags: String,
+    uid: Option<String>,
+}
+
+impl TaskValues {
+    fn new(task: &Task) -> Self {
+        let time = |time: &Option<DateTime<Local>>| time.map(|time| time.to_rfc3339());
+        Self {
+            description: task.description.clone(),
+            is_done: task.is_done,
+            created_at: time(&task.created_at),
+            completed_at: time(&task.completed_at),
+            modified_at: time(&task.modified_at),
+            due_date: task.due_date.map(|due| due.format("%Y-%m-%d").to_string()),
+            recurrence: task.recurrence.as_ref().map(Recurrence::token),
+            priority: task.priority.map(|priority| priority.to_string()),
+            tags: task.tags.join(" "),
+            uid: task.uid.clone(),
+        }
+    }
+
+    fn params(&self) -> Vec<&dyn ToSql> {
+        vec![
+            &self.description,
+            &self.is_done,
+            &self.created_at,
+            &self.completed_at,
+            &self.modified_at,
+            &self.due_date,
+            &self.recurrence,
+            &self.priority,
+            &self.tags,
+            &self.uid,
+        ]
+    }
+}
+
+/// Inserts a row holding a task into the given table, with the given values for its other columns
+fn insert_task(
+    transaction: &Transaction,
+    table: &str,
+    columns: &[(&str, &dyn ToSql)],
+    task: &Task,
+) -> Result<(), Error> {
+    let values = TaskValues::new(task);
+    let mut params: Vec<&dyn ToSql> = columns.iter().map(|(_, value)| *value).collect();
+    params.extend(values.params());
+
+    let names: Vec<&str> = columns.iter().map(|(name, _)| *name).collect();
+    let placeholders = vec!["?"; params.len()].join(", ");
+    transaction.execute(
+        &format!(
+            "INSERT INTO {} ({}, {}) VALUES ({})",
+            table,
+            names.join(", "),
+            TASK_COLUMNS,
+            placeholders
+        ),
+        params,
+    )?;
+    Ok(())
+}
+
+/// Replaces the stored tasks of the given group
+fn write_group_tasks(
+    transaction: &Transaction,
+    group_position: usize,
+    group: &TaskGroup,
+) -> Result<(), Error> {
+    transaction.execute(
+        "DELETE FROM tasks WHERE group_position = ?1",
+        params![group_position as i64],
+    )?;
+    for (position, task) in group.tasks.iter().enumerate() {
+        let columns: [(&str, &dyn ToSql); 2] = [
+            ("group_position", &(group_position as i64)),
+            ("position", &(position as i64)),
+        ];
+        insert_task(transaction, "tasks", &columns, task)?;
+    }
+    Ok(())
+}
+
+fn write_groups(transaction: &Transaction, task_groups: &[TaskGroup]) -> Result<(), Error> {
+    transaction.execute("DELETE FROM groups", NO_PARAMS)?;
+    transaction.execute("DELETE FROM tasks", NO_PARAMS)?;
+    for (position, group) in task_groups.iter().enumerate() {
+        transaction.execute(
+            "INSERT INTO groups (position, name) VALUES (?1, ?2)",
+            params![position as i64, group.name],
+        )?;
+        write_group_tasks(transaction, position, group)?;
+    }
+    Ok(())
+}
+
+/// Writes the archive, given the one that's stored if it's known.
+/// Tasks are only ever added to the end of the archive or restored from it,
+/// so when the stored archive is the start of the new one, only the new entries are added
+fn write_archive(
+    transaction: &Transaction,
+    before: Option<&Archive>,
+    after: &Archive,
+) -> Result<(), Error> {
+    let kept = match before {
+        Some(before) if after.entries.starts_with(&before.entries) => before.entries.len(),
+        _ => {
+            transaction.execute("DELETE FROM archive", NO_PARAMS)?;
+            0
+        }
+    };
+
+    for (position, entry) in after.entries.iter().enumerate().skip(kept) {
+        let completed_at = entry.completed_at.to_rfc3339();
+        let columns: [(&str, &dyn ToSql); 3] = [
+            ("position", &(position as i64)),
+            ("group_name", &entry.group_name),
+            ("archived_completed_at", &completed_at),
+        ];
+        insert_task(transaction, "archive", &columns, &entry.task)?;
+    }
+    Ok(())
+}
+
+fn write_queries(
+    transaction: &Transaction,
+    saved_queries: &BTreeMap<String, String>,
+) -> Result<(), Error> {
+    transaction.execute("DELETE FROM queries", NO_PARAMS)?;
+    for (name, query) in saved_queries {
+        transaction.execute(
+            "INSERT INTO queries (name, query) VALUES (?1, ?2)",
+            params![name, query],
+        )?;
+    }
+    Ok(())
+}
+
+impl SqliteStorage {
+    /// Opens the database of the given config folder, creating it if needed.
+    /// New databases are filled with the list of the folder's `dodo.ron`, if there's one
+    pub fn open(config_path: &Path) -> Result<Self, Error> {
+        let database_path = config_path.join(DATABASE_FILE_NAME);
+        let is_new = !database_path.exists();
+
+        let mut storage = Self::with_connection(Connection::open(&database_path)?)?;
+        if is_new && config_path.join(RON_FILE_NAME).exists() {
+            let list = RonStorage::new(config_path.into(), true).load()?;
+            storage.save(&list)?;
+            println!(
+                "{}: moved the list of `{}` to `{}`. `{}` is kept as a backup.",
+                "info".yellow(),
+                RON_FILE_NAME,
+                DATABASE_FILE_NAME,
+                RON_FILE_NAME
+            );
+        }
+
+        Ok(storage)
+    }
+
+    /// Uses the given connection, bringing its database up to date
+    pub fn with_connection(mut connection: Connection) -> Result<Self, Error> {
+        let version: i64 =
+            connection.query_row("PRAGMA user_version", NO_PARAMS, |row| row.get(0))?;
+        if version > SCHEMA_VERSION {
+            return Err(Error::StorageError(format!(
+                "`{}` was created by a newer version of dodo",
+                DATABASE_FILE_NAME
+            )));
+        }
+
+        let transaction = connection.transaction()?;
+        for migration in &MIGRATIONS[version as usize..] {
+            transaction.execute_batch(migration)?;
+        }
+        transaction.pragma_update(None, "user_version", &SCHEMA_VERSION)?;
+        transaction.commit()?;
+
+        Ok(Self {
+            connection,
+            data_version: None,
+        })
+    }
+
+    /// Writes what changed between the two lists in a single transaction. Fails with
+    /// `Error::ExternalChanges` if the database was changed elsewhere since it was last loaded or saved
+    fn write(&mut self, before: Option<&StoredList>, after: &StoredList) -> Result<(), Error> {
+        let transaction = self
+            .connection
+            .transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let data_version = data_version(&transaction)?;
+        if matches!(self.data_version, Some(loaded) if loaded != data_version) {
+            return Err(Error::ExternalChanges);
+        }
+
+        write_changes(&transaction, before, after)?;
+        transaction.commit()?;
+        self.data_version = Some(data_version);
+        Ok(())
+    }
+}
+
+/// Writes what changed between the two lists.
+/// Everything is written when the stored list isn't known
+fn write_changes(
+    transaction: &Transaction,
+    before: Option<&StoredList>,
+    after: &StoredList,
+) -> Result<(), Error> {
+    let before = match before {
+        Some(before) => before,
+        None => {
+            write_groups(transaction, &after.task_groups)?;
+            write_archive(transaction, None, &after.archive)?;
+            write_queries(transaction, &after.saved_queries)?;
+            return Ok(());
+        }
+    };
+
+    // When the groups are the same, only the tasks of the groups that changed are written
+    let same_groups = before.task_groups.len() == after.task_groups.len()
+        && before
+            .task_groups
+            .iter()
+            .zip(&after.task_groups)
+            .all(|(old, new)| old.name == new.name);
+    if same_groups {
+        let groups = before.task_groups.iter().zip(&after.task_groups);
+        for (position, (old, new)) in groups.enumerate() {
+            if old.tasks != new.tasks {
+                write_group_tasks(transaction, position, new)?;
+            }
+        }
+    } else {
+        write_groups(transaction, &after.task_groups)?;
+    }
+
+    if before.archive != after.archive {
+        write_archive(transaction, Some(&before.archive), &after.archive)?;
+    }
+    if before.saved_queries != after.saved_queries {
+        write_queries(transaction, &after.saved_queries)?;
+    }
+
+    Ok(())
+}
+
+impl Storage for SqliteStorage {
+    fn load(&mut self) -> Result<StoredList, Error> {
+        let mut task_groups: Vec<TaskGroup> = vec![];
+        let mut statement = self
+            .connection
+            .prepare("SELECT name FROM groups ORDER BY position")?;
+        let names = statement.query_map(NO_PARAMS, |row| row.get(0))?;
+        for name in names {
+            task_groups.push(TaskGroup {
+                name: name?,
+                tasks: vec![],
+            });
+        }
+
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT group_position, {} FROM tasks ORDER BY group_position, position",
+            TASK_COLUMNS
+        ))?;
+        let mut rows = statement.query(NO_PARAMS)?;
+        while let Some(row) = rows.next()? {
+            let group_position: i64 = row.get(0)?;
+            let task = task_from_row(row, 1)?;
+            match task_groups.get_mut(group_position as usize) {
+                Some(group) => group.tasks.push(task),
+                None => {
+                    return Err(Error::StorageError(format!(
+                        "a task is part of group {}, which doesn't exist",
+                        group_position + 1
+                    )))
+                }
+            }
+        }
+
+        let mut archive = Archive::default();
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT group_name, archived_completed_at, {} FROM archive ORDER BY position",
+            TASK_COLUMNS
+        ))?;
+        let mut rows = statement.query(NO_PARAMS)?;
+        while let Some(row) = rows.next()? {
+            let completed_at = parse_time("archived_completed_at", row.get(1)?)?;
+            archive.entries.push(ArchivedTask {
+                group_name: row.get(0)?,
+                task: task_from_row(row, 2)?,
+                completed_at: completed_at.unwrap_or_else(Local::now),
+            });
+        }
+
+        let mut saved_queries = BTreeMap::new();
+        let mut statement = self.connection.prepare("SELECT name, query FROM queries")?;
+        let queries = statement.query_map(NO_PARAMS, |row| Ok((row.get(0)?, row.get(1)?)))?;
+        for query in queries {
+            let (name, query) = query?;
+            saved_queries.insert(name, query);
+        }
+
+        self.data_version = Some(data_version(&self.connection)?);
+        Ok(StoredList {
+            task_groups,
+            archive,
+            saved_queries,
+        })
+    }
+
+    fn save(&mut self, list: &StoredList) -> Result<(), Error> {
+        self.write(None, list)
+    }
+
+    fn save_changes(&mut self, before: &StoredList, after: &StoredList) -> Result<(), Error> {
+        self.write(Some(before), after)
+    }
+
+    fn is_incremental(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn storage() -> Result<SqliteStorage, Error> {
+        SqliteStorage::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn list() -> StoredList {
+        let mut report =
+            Task::from_input("Write the report +work priority:high every:mon due:2021-03-01")
+                .unwrap();
+        report.uid();
+        let mut groceries = Task::new("Buy groceries".into());
+        groceries.flip();
+
+        let mut list = StoredList {
+            task_groups: vec![
+                TaskGroup {
+                    name: "Work".into(),
+                    tasks: vec![report, Task::new("Answer emails".into())],
+                },
+                TaskGroup {
+                    name: "Home".into(),
+                    tasks: vec![groceries.clone()],
+                },
+            ],
+            ..Default::default()
+        };
+        list.archive.entries.push(ArchivedTask {
+            group_name: "Home".into(),
+            task: groceries,
+            completed_at: Local::now(),
+        });
+        list.saved_queries
+            .insert("urgent".into(), "due:today".into());
+        list
+    }
+
+    #[test]
+    fn round_trip() -> Result<(), Error> {
+        let mut storage = storage()?;
+        assert_eq!(storage.load()?, StoredList::default());
+
+        let list = list();
+        storage.save(&list)?;
+        assert_eq!(storage.load()?, list);
+        Ok(())
+    }
+
+    #[test]
+    fn saving_changes() -> Result<(), Error> {
+        let mut storage = storage()?;
+        let before = list();
+        storage.save(&before)?;
+
+        // Groups whose tasks didn't change aren't written again, so this edit is kept
+        storage.connection.execute(
+            "UPDATE tasks SET description = 'Edited' WHERE group_position = 0 AND position = 1",
+            NO_PARAMS,
+        )?;
+
+        let mut after = before.clone();
+        after.task_groups[1]
+            .tasks
+            .push(Task::new("Water the plants".into()));
+        after.archive.entries.push(ArchivedTask {
+            group_name: "Work".into(),
+            task: Task::new("Book the flights".into()),
+            completed_at: Local::now(),
+        });
+        storage.save_changes(&before, &after)?;
+
+        let loaded = storage.load()?;
+        assert_eq!(loaded.task_groups[0].tasks[1].description, "Edited");
+        assert_eq!(loaded.task_groups[1], after.task_groups[1]);
+        assert_eq!(loaded.archive, after.archive);
+
+        // Moving groups around writes all of them
+        let before = loaded;
+        let mut after = before.clone();
+        after.task_groups.swap(0, 1);
+        after.archive.entries.remove(0);
+        after.saved_queries.clear();
+        storage.save_changes(&before, &after)?;
+        assert_eq!(storage.load()?, after);
+        Ok(())
+    }
+
+    #[test]
+    fn newer_databases() -> Result<(), Error> {
+        let connection = Connection::open_in_memory()?;
+        connection.pragma_update(None, "user_version", &(SCHEMA_VERSION + 1))?;
+        assert!(SqliteStorage::with_connection(connection).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn detecting_external_changes() -> Result<(), Error> {
+        let folder =
+            std::env::temp_dir().join(format!("dodo-sqlite-storage-{}", std::process::id()));
+        std::fs::create_dir_all(&folder)?;
+        let result = (|| {
+            let mut ours = SqliteStorage::open(&folder)?;
+            let mut theirs = SqliteStorage::open(&folder)?;
+            let loaded = ours.load()?;
+            theirs.load()?;
+
+            theirs.save(&list())?;
+            assert!(matches!(ours.save(&loaded), Err(Error::ExternalChanges)));
+
+            // Once the changes are loaded, saving works again
+            ours.load()?;
+            ours.save(&loaded)
+        })();
+        std::fs::remove_dir_all(&folder)?;
+        result
+    }
+}
@@ -0,0 +1,311 @@
+use std::{fs, path::Path, process};
+
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::Error;
+use crate::merge;
+use crate::storage::{self, Backend, StoredList};
+use crate::todolist::TodoList;
+
+static GITIGNORE_FILE_NAME: &str = ".gitignore";
+static REMOTE_NAME: &str = "origin";
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(default)]
+/// How the list is synced with git, stored in the `sync` section of the configuration
+pub struct SyncSettings {
+    /// The URL or path of the repository the list is pulled from and pushed to
+    pub remote: Option<String>,
+    /// Whether every save is committed, once the config folder is a git repository
+    pub auto_commit: bool,
+}
+
+impl Default for SyncSettings {
+    fn default() -> Self {
+        Self {
+            remote: None,
+            auto_commit: true,
+        }
+    }
+}
+
+/// Runs git in the given folder, returning what it printed
+fn git(folder: &Path, args: &[&str]) -> Result<String, Error> {
+    let output = process::Command::new("git")
+        .arg("-C")
+        .arg(folder)
+        .args(args)
+        .output()
+        .map_err(|err| Error::SyncError(format!("could not run git: {}", err)))?;
+
+    if !output.status.success() {
+        return Err(Error::SyncError(format!(
+            "`git {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Runs git for something that may not exist, such as a branch, returning None if it doesn't
+fn git_query(folder: &Path, args: &[&str]) -> Option<String> {
+    git(folder, args).ok()
+}
+
+/// Whether the given folder is synced with git
+pub fn is_repository(folder: &Path) -> bool {
+    folder.join(".git").exists()
+}
+
+/// Turns the folder into a git repository that only tracks the files of the list,
+/// leaving out the configuration and the REPL's history
+fn init(folder: &Path) -> Result<(), Error> {
+    git(folder, &["init", "--quiet"])?;
+
+    let mut patterns = vec!["*".to_string(), format!("!{}", GITIGNORE_FILE_NAME)];
+    patterns.extend(
+        storage::ron_file_names()
+            .iter()
+            .map(|file_name| format!("!{}", file_name)),
+    );
+    fs::write(folder.join(GITIGNORE_FILE_NAME), patterns.join("\n") + "\n")?;
+
+    commit(folder, "Start syncing the list")
+}
+
+/// Builds a commit message out of the summaries of the commands that changed the list
+pub fn commit_message(changes: &[String]) -> String {
+    match changes {
+        [] => "Update the list".into(),
+        [change] => change.clone(),
+        changes => format!("{} changes\n\n{}", changes.len(), changes.join("\n")),
+    }
+}
+
+/// Prefixes a command that records commits with an author, which is made up
+/// for users who didn't configure git
+fn with_identity<'a>(folder: &Path, args: &[&'a str]) -> Vec<&'a str> {
+    let mut identity = vec![];
+    if git_query(folder, &["config", "user.name"]).is_none() {
+        identity.extend(&["-c", "user.name=dodo"]);
+    }
+    if git_query(folder, &["config", "user.email"]).is_none() {
+        identity.extend(&["-c", "user.email=dodo@localhost"]);
+    }
+    identity.extend(args);
+    identity
+}
+
+/// Commits every change to the files of the list, if there's any, or concludes a merge
+pub fn commit(folder: &Path, message: &str) -> Result<(), Error> {
+    git(folder, &["add", "--all"])?;
+    let is_merging = folder.join(".git").join("MERGE_HEAD").exists();
+    if !is_merging && git(folder, &["status", "--porcelain"])?.is_empty() {
+        return Ok(());
+    }
+
+    git(
+        folder,
+        &with_identity(folder, &["commit", "--quiet", "--message", message]),
+    )?;
+    Ok(())
+}
+
+/// Reads the list as it was at the given revision
+fn list_at(folder: &Path, revision: &str) -> Result<StoredList, Error> {
+    let contents: Vec<Option<String>> = storage::ron_file_names()
+        .iter()
+        .map(|file_name| git_query(folder, &["show", &format!("{}:{}", revision, file_name)]))
+        .collect();
+    storage::parse_ron_files(&contents)
+}
+
+/// Merges the commits of `upstream` into the local ones task by task, so that changes to
+/// different tasks are all kept, and commits the result.
+/// `base` is the last commit they share, or None if they don't share any
+fn merge_upstream(
+    list: &mut TodoList,
+    folder: &Path,
+    upstream: &str,
+    base: Option<&str>,
+) -> Result<(), Error> {
+    let base_list = match base {
+        Some(base) => list_at(folder, base)?,
+        None => StoredList::default(),
+    };
+    let merge = merge::merge(&base_list, &list.stored(), &list_at(folder, upstream)?);
+
+    // The merge is started without touching the files, which are then replaced by the merged list
+    let mut args = vec!["merge", "--quiet", "--no-commit", "--strategy", "ours"];
+    if base.is_none() {
+        args.push("--allow-unrelated-histories");
+    }
+    args.push(upstream);
+    git(folder, &with_identity(folder, &args))?;
+
+    list.storage.save(&merge.list)?;
+    list.set_stored(merge.list);
+    commit(folder, &format!("Merge the changes of {}", upstream))?;
+
+    match merge.conflicts {
+        0 => println!("{}: merged the changes of the remote.", "info".yellow()),
+        conflicts => println!(
+            "{}: merged the changes of the remote. {} task(s) were changed in both places, the local changes were kept.",
+            "info".yellow(),
+            conflicts
+        ),
+    }
+    Ok(())
+}
+
+/// Commits the list, then pulls the commits of the remote set in `sync.remote` and pushes the
+/// local ones. The config folder is turned into a git repository the first time
+pub fn run(list: &mut TodoList) -> Result<(), Error> {
+    let folder = match list.config_path.clone() {
+        Some(folder) => folder,
+        None => return Err(Error::SyncError("the list isn't stored in a folder".into())),
+    };
+    if list.config.storage != Backend::Ron {
+        return Err(Error::SyncError(
+            "only lists stored in RON files can be synced. Use `dodo config set storage ron`"
+                .into(),
+        ));
+    }
+
+    if !is_repository(&folder) {
+        init(&folder)?;
+        println!(
+            "{}: the list in `{}` is now versioned with git.",
+            "info".yellow(),
+            folder.display()
+        );
+    }
+    commit(&folder, &commit_message(&list.unsaved_changes))?;
+
+    let remote = match &list.config.sync.remote {
+        Some(remote) => remote.clone(),
+        None => {
+            println!(
+                "{}: set a remote with `dodo config set sync.remote URL` to share the list.",
+                "info".yellow()
+            );
+            return Ok(());
+        }
+    };
+    match git_query(&folder, &["remote", "get-url", REMOTE_NAME]) {
+        Some(url) if url == remote => {}
+        Some(_) => {
+            git(&folder, &["remote", "set-url", REMOTE_NAME, &remote])?;
+        }
+        None => {
+            git(&folder, &["remote", "add", REMOTE_NAME, &remote])?;
+        }
+    }
+
+    let branch = git(&folder, &["symbolic-ref", "--short", "HEAD"])?;
+    let upstream = format!("{}/{}", REMOTE_NAME, branch);
+    git(&folder, &["fetch", "--quiet", REMOTE_NAME])?;
+
+    // Nothing is pulled when the remote doesn't have the branch yet, e.g. when it was just created
+    if let Some(theirs) = git_query(&folder, &["rev-parse", "--verify", "--quiet", &upstream]) {
+        let ours = git(&folder, &["rev-parse", "HEAD"])?;
+        let base = git_query(&folder, &["merge-base", "HEAD", &upstream]);
+
+        if base.as_deref() == Some(ours.as_str()) && ours != theirs {
+            git(&folder, &["merge", "--quiet", "--ff-only", &upstream])?;
+            list.reload()?;
+            println!("{}: pulled the changes of the remote.", "info".yellow());
+        } else if base.as_deref() != Some(theirs.as_str()) {
+            merge_upstream(list, &folder, &upstream, base.as_deref())?;
+        }
+    }
+
+    git(
+        &folder,
+        &["push", "--quiet", "--set-upstream", REMOTE_NAME, &branch],
+    )?;
+    println!("{}: synced the list with `{}`.", "info".yellow(), remote);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{convert::TryFrom, path::PathBuf};
+
+    use super::*;
+    use crate::command::Command;
+
+    /// Opens the list in the given folder, synced with the given remote
+    fn open(folder: PathBuf, remote: &Path) -> Result<TodoList, Error> {
+        fs::create_dir_all(&folder)?;
+        let mut list = TodoList::try_from(folder)?;
+        list.evaluate(Command::ConfigSet(
+            "sync.remote".into(),
+            remote.display().to_string(),
+        ));
+        list.save()?;
+        Ok(list)
+    }
+
+    fn edit_and_sync(list: &mut TodoList, commands: Vec<Command>) -> Result<(), Error> {
+        for command in commands {
+            list.evaluate(command);
+        }
+        list.save()?;
+        run(list)
+    }
+
+    /// Two lists that start separately, then change the same group while syncing
+    fn sync_two_lists(root: &Path) -> Result<(StoredList, StoredList), Error> {
+        let remote = root.join("remote.git");
+        fs::create_dir_all(&remote)?;
+        git(&remote, &["init", "--quiet", "--bare"])?;
+
+        let mut laptop = open(root.join("laptop"), &remote)?;
+        edit_and_sync(
+            &mut laptop,
+            vec![
+                Command::AddGroup("Work".into()),
+                Command::AddTask("Write the report".into(), 1),
+            ],
+        )?;
+
+        let mut desktop = open(root.join("desktop"), &remote)?;
+        run(&mut desktop)?;
+
+        edit_and_sync(&mut laptop, vec![Command::AddTask("Deploy".into(), 1)])?;
+        edit_and_sync(&mut desktop, vec![Command::FlipTask(1, 1)])?;
+        run(&mut laptop)?;
+
+        Ok((laptop.stored(), desktop.stored()))
+    }
+
+    #[test]
+    fn syncing_through_a_remote() -> Result<(), Error> {
+        let root = std::env::temp_dir().join(format!("dodo-sync-{}", process::id()));
+        let result = sync_two_lists(&root);
+        fs::remove_dir_all(&root)?;
+
+        let (laptop, desktop) = result?;
+        assert_eq!(laptop, desktop);
+        let tasks = &laptop.task_groups[0].tasks;
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].description, "Write the report");
+        assert!(tasks[0].is_done);
+        assert_eq!(tasks[1].description, "Deploy");
+
+        Ok(())
+    }
+
+    #[test]
+    fn commit_messages() {
+        assert_eq!(commit_message(&[]), "Update the list");
+        assert_eq!(commit_message(&["Flip task 1.1".into()]), "Flip task 1.1");
+        assert_eq!(
+            commit_message(&["Flip task 1.1".into(), "Add group `Home`".into()]),
+            "2 changes\n\nFlip task 1.1\nAdd group `Home`"
+        );
+    }
+}
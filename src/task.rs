@@ -1,15 +1,229 @@
-use std::fmt;
+use std::{fmt, str::FromStr};
 
+use chrono::{DateTime, Duration, Local, NaiveDate};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+use crate::display::{self, DisplayOptions, DEFAULT_DATE_FORMAT};
+use crate::recurrence::Recurrence;
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Default)]
 /// Represents a task and wether or not it's been concluded
 pub struct Task {
     pub description: String,
     pub is_done: bool,
+    /// When the task was created.
+    /// None for tasks created before timestamps were recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Local>>,
+    /// When the task was marked as done, if it is done
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Local>>,
+    /// When the task was last changed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified_at: Option<DateTime<Local>>,
+    /// When the task should be done by
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due_date: Option<NaiveDate>,
+    /// How often the task repeats, if it does
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recurrence: Option<Recurrence>,
+    /// How important the task is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+    /// Free-form labels, written as `+label`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Identifies the task in other apps, such as calendars. Generated the first time it's needed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+/// How important a task is
+pub enum Priority {
+    Low,
+    Medium,
+    High,
+}
+
+impl FromStr for Priority {
+    type Err = String;
+
+    fn from_str(priority: &str) -> Result<Self, Self::Err> {
+        match priority.to_lowercase().as_str() {
+            "low" | "l" => Ok(Priority::Low),
+            "medium" | "med" | "m" => Ok(Priority::Medium),
+            "high" | "h" => Ok(Priority::High),
+            _ => Err(format!(
+                "`{}` is not a valid priority. Expected `low`, `medium` or `high`",
+                priority
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Priority::Low => write!(f, "low"),
+            Priority::Medium => write!(f, "medium"),
+            Priority::High => write!(f, "high"),
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+/// Parses dates as typed by the user, e.g. `2021-03-14`, `today` or `tomorrow`
+pub fn parse_date(date: &str) -> Result<NaiveDate, String> {
+    let today = Local::now().date_naive();
+    match date {
+        "today" => Ok(today),
+        "tomorrow" => Ok(today + Duration::days(1)),
+        date => NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map_err(|_| format!("`{}` is not a valid date. Expected YYYY-MM-DD", date)),
+    }
+}
+
+impl Task {
+    /// Creates a new, not yet done, task
+    pub fn new(description: String) -> Self {
+        let now = Local::now();
+        Self {
+            description,
+            is_done: false,
+            created_at: Some(now),
+            completed_at: None,
+            modified_at: Some(now),
+            due_date: None,
+            recurrence: None,
+            priority: None,
+            tags: vec![],
+            uid: None,
+        }
+    }
+
+    /// Creates a new task from user input, extracting inline attributes such as
+    /// `due:2021-03-14`, `every:mon,thu`, `after:3d`, `priority:high` or `+tag` from the description
+    pub fn from_input(input: &str) -> Result<Self, String> {
+        let mut task = Task::new(String::new());
+        let mut description = vec![];
+
+        for word in input.split(' ').filter(|x| !x.is_empty()) {
+            if let Some(date) = word.strip_prefix("due:") {
+                task.due_date = Some(parse_date(date)?);
+            } else if let Some(priority) = word.strip_prefix("priority:") {
+                task.priority = Some(priority.parse()?);
+            } else if let Some(tag) = word.strip_prefix('+').filter(|tag| !tag.is_empty()) {
+                if !task.tags.iter().any(|existing| existing == tag) {
+                    task.tags.push(tag.into());
+                }
+            } else if let Some(recurrence) = Recurrence::from_token(word) {
+                task.recurrence = Some(recurrence?);
+            } else {
+                description.push(word);
+            }
+        }
+
+        task.description = description.join(" ");
+        Ok(task)
+    }
+
+    /// Writes the task the way it would be typed to create it, the inverse of `from_input`
+    pub fn to_input(&self) -> String {
+        let mut words = vec![self.description.clone()];
+        words.extend(self.tags.iter().map(|tag| format!("+{}", tag)));
+        words.extend(
+            self.due_date
+                .map(|due| format!("due:{}", due.format("%Y-%m-%d"))),
+        );
+        words.extend(self.recurrence.as_ref().map(Recurrence::token));
+        words.extend(self.priority.map(|priority| format!("priority:{}", priority)));
+        words.retain(|word| !word.is_empty());
+        words.join(" ")
+    }
+
+    /// Returns the task's unique identifier, generating it if the task doesn't have one yet
+    pub fn uid(&mut self) -> &str {
+        self.uid.get_or_insert_with(|| Uuid::new_v4().to_string())
+    }
+
+    /// If this is a recurring task, returns its next occurrence
+    pub fn next_occurrence(&self) -> Option<Task> {
+        let recurrence = self.recurrence.as_ref()?;
+        let completed_on = self
+            .completed_at
+            .unwrap_or_else(Local::now)
+            .naive_local()
+            .date();
+
+        let mut next = Task::new(self.description.clone());
+        next.due_date = Some(recurrence.next_due_date(self.due_date, completed_on));
+        next.recurrence = Some(recurrence.clone());
+        next.priority = self.priority;
+        next.tags = self.tags.clone();
+        Some(next)
+    }
+
+    /// Flips the task from not done to done or vice-versa, updating its timestamps
+    pub fn flip(&mut self) {
+        let now = Local::now();
+        self.is_done = !self.is_done;
+        self.completed_at = if self.is_done { Some(now) } else { None };
+        self.modified_at = Some(now);
+    }
+
+    /// Writes the task according to the given display options
+    pub fn fmt_with(&self, f: &mut fmt::Formatter<'_>, options: &DisplayOptions) -> fmt::Result {
+        let date_format = options.date_format.as_deref().unwrap_or(DEFAULT_DATE_FORMAT);
+
+        write!(
+            f,
+            "[{}] - {}",
+            if self.is_done { "x" } else { " " },
+            self.description
+        )?;
+
+        for tag in &self.tags {
+            write!(f, " +{}", tag)?;
+        }
+
+        let mut attributes = vec![];
+        if let Some(due_date) = &self.due_date {
+            attributes.push(format!("due {}", display::format_date(*due_date, date_format)));
+        }
+        if let Some(recurrence) = &self.recurrence {
+            attributes.push(recurrence.to_string());
+        }
+        if let Some(priority) = &self.priority {
+            attributes.push(format!("{} priority", priority));
+        }
+        if !attributes.is_empty() {
+            write!(f, " ({})", attributes.join(", "))?;
+        }
+
+        if options.show_timestamps {
+            let date = |time: &Option<DateTime<Local>>| match time {
+                Some(time) => display::format_date(time.date_naive(), date_format),
+                None => "unknown".into(),
+            };
+            write!(f, " (created {}", date(&self.created_at))?;
+            if self.is_done {
+                write!(f, ", done {}", date(&self.completed_at))?;
+            }
+            if self.modified_at.is_some() && self.modified_at != self.created_at {
+                write!(f, ", modified {}", date(&self.modified_at))?;
+            }
+            write!(f, ")")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Width, in characters, of the progress bar shown next to each group's name
+const PROGRESS_BAR_WIDTH: usize = 10;
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 /// A group of tasks
 pub struct TaskGroup {
     /// The group's name
@@ -18,24 +232,85 @@ pub struct TaskGroup {
     pub tasks: Vec<Task>,
 }
 
-impl fmt::Display for TaskGroup {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "{}", self.name)?;
-
-        for (i, task) in self.tasks.iter().enumerate() {
-            writeln!(
-                f,
-                "\t{}. [{}] - {}",
-                i + 1,
-                if task.is_done { "x" } else { " " },
-                task.description
-            )?;
+impl TaskGroup {
+    /// Returns how many tasks of this group are done, and how many tasks there are
+    pub fn progress(&self) -> (usize, usize) {
+        let done = self.tasks.iter().filter(|task| task.is_done).count();
+        (done, self.tasks.len())
+    }
+
+    /// The percentage of tasks that are done, rounded to the nearest integer
+    pub fn completion_rate(&self) -> usize {
+        match self.progress() {
+            (_, 0) => 0,
+            (done, total) => (done * 200 + total) / (total * 2),
+        }
+    }
+
+    /// The group's name followed by its progress, e.g. `College [3/5] ██████░░░░ 60%`
+    pub fn header(&self) -> String {
+        let (done, total) = self.progress();
+        if total == 0 {
+            return self.name.clone();
+        }
+
+        let filled = (done * PROGRESS_BAR_WIDTH * 2 + total) / (total * 2);
+        format!(
+            "{} [{}/{}] {}{} {}%",
+            self.name,
+            done,
+            total,
+            "█".repeat(filled),
+            "░".repeat(PROGRESS_BAR_WIDTH - filled),
+            self.completion_rate()
+        )
+    }
+
+    /// Whether the group has tasks and all of them are done
+    pub fn is_completed(&self) -> bool {
+        !self.tasks.is_empty() && self.tasks.iter().all(|task| task.is_done)
+    }
+
+    /// Writes the group according to the given display options
+    pub fn fmt_with(&self, f: &mut fmt::Formatter<'_>, options: &DisplayOptions) -> fmt::Result {
+        self.fmt_tasks(f, self.tasks.iter().enumerate(), options)
+    }
+
+    /// Writes the group with only the given tasks, along with their zero-indexed positions,
+    /// so that the numbers shown still refer to the right tasks
+    pub fn fmt_tasks<'a, I>(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        tasks: I,
+        options: &DisplayOptions,
+    ) -> fmt::Result
+    where
+        I: Iterator<Item = (usize, &'a Task)>,
+    {
+        writeln!(f, "{}", self.header())?;
+
+        for (i, task) in tasks {
+            write!(f, "\t{}. ", i + 1)?;
+            task.fmt_with(f, options)?;
+            writeln!(f)?;
         }
 
         write!(f, "")
     }
 }
 
+impl fmt::Display for Task {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_with(f, &DisplayOptions::default())
+    }
+}
+
+impl fmt::Display for TaskGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_with(f, &DisplayOptions::default())
+    }
+}
+
 #[cfg(test)]
 /// Testing RON serialization and deserialization
 mod ron_tests {
@@ -50,7 +325,7 @@ mod ron_tests {
 
         assert_eq!(
             groups,
-            "Uni\n\t1. [x] - Study for the Physics test\n\t2. [x] - Study Monads\n\t3. [ ] - Finish the Compilers project\nChores\n\t1. [x] - Clean the house\n\t2. [x] - Unlearn JavaScript\n\t3. [ ] - Make Python statically typed\n"
+            "Uni [2/3] ███████░░░ 67%\n\t1. [x] - Study for the Physics test\n\t2. [x] - Study Monads\n\t3. [ ] - Finish the Compilers project\nChores [2/3] ███████░░░ 67%\n\t1. [x] - Clean the house\n\t2. [x] - Unlearn JavaScript\n\t3. [ ] - Make Python statically typed\n"
         )
     }
 
@@ -61,14 +336,17 @@ mod ron_tests {
                 Task {
                     description: "Clean the house".into(),
                     is_done: true,
+                    ..Default::default()
                 },
                 Task {
                     description: "Unlearn JavaScript".into(),
                     is_done: true,
+                    ..Default::default()
                 },
                 Task {
                     description: "Make Python statically typed".into(),
                     is_done: false,
+                    ..Default::default()
                 },
             ],
         }
@@ -81,14 +359,17 @@ mod ron_tests {
                 Task {
                     description: "Study for the Physics test".into(),
                     is_done: true,
+                    ..Default::default()
                 },
                 Task {
                     description: "Study Monads".into(),
                     is_done: true,
+                    ..Default::default()
                 },
                 Task {
                     description: "Finish the Compilers project".into(),
                     is_done: false,
+                    ..Default::default()
                 },
             ],
         }
@@ -158,4 +439,60 @@ mod ron_tests {
 
         assert_eq!(deserialized_group, vec![sample_group_1(), sample_group_2()]);
     }
+
+    #[test]
+    fn task_timestamps_round_trip() {
+        let mut task = Task::new("Write the report".into());
+        task.flip();
+
+        let serialized_data = ron::ser::to_string(&task).unwrap();
+        let deserialized_task: Task = ron::de::from_str(&serialized_data).unwrap();
+
+        assert_eq!(task, deserialized_task);
+        assert!(deserialized_task.completed_at.is_some());
+    }
+
+    #[test]
+    fn task_from_input() {
+        let task = Task::from_input("Deploy review every:mon,thu due:2021-03-01").unwrap();
+
+        assert_eq!(task.description, "Deploy review");
+        assert_eq!(task.due_date, NaiveDate::from_ymd_opt(2021, 3, 1));
+        assert_eq!(task.recurrence, Some("mon,thu".parse().unwrap()));
+
+        assert!(Task::from_input("Deploy review due:someday").is_err());
+    }
+
+    #[test]
+    fn recurring_task_display() {
+        let task = Task::from_input("Deploy review every:mon,thu due:2021-03-01").unwrap();
+        let group = TaskGroup {
+            name: "Work".into(),
+            tasks: vec![task],
+        };
+
+        assert_eq!(
+            group.to_string(),
+            "Work [0/1] ░░░░░░░░░░ 0%\n\t1. [ ] - Deploy review (due 2021-03-01, every mon,thu)\n"
+        );
+    }
+
+    #[test]
+    fn tags_and_priority() {
+        let task = Task::from_input("Fix login +backend priority:high +auth +backend").unwrap();
+
+        assert_eq!(task.description, "Fix login");
+        assert_eq!(task.priority, Some(Priority::High));
+        assert_eq!(task.tags, vec!["backend", "auth"]);
+        assert_eq!(
+            TaskGroup {
+                name: "Work".into(),
+                tasks: vec![task],
+            }
+            .to_string(),
+            "Work [0/1] ░░░░░░░░░░ 0%\n\t1. [ ] - Fix login +backend +auth (high priority)\n"
+        );
+
+        assert!(Task::from_input("Fix login priority:urgent").is_err());
+    }
 }
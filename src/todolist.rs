@@ -1,73 +1,207 @@
 use std::{
+    collections::BTreeMap,
     convert::TryFrom,
-    fmt,
-    fs::{self, File},
-    io::{BufWriter, Write},
-    path::PathBuf,
+    fmt, fs,
+    path::{Path, PathBuf},
 };
 
+use chrono::{Local, NaiveDate};
 use colored::Colorize;
-use ron;
 
-use crate::command::Command;
-use crate::config_path;
+use crate::archive::{Archive, ArchivedTask};
+use crate::command::{Command, TaskRef};
+use crate::config::Config;
+use crate::display::DisplayOptions;
 use crate::errors::{self, Error};
+use crate::filter;
+use crate::formats::{self, ConversionOptions, Format};
+use crate::merge;
+use crate::stats::Statistics;
+use crate::storage::{self, MemoryStorage, SharedStorage, StoredList};
+use crate::sync;
 use crate::task::{Task, TaskGroup};
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct TodoList {
     pub task_groups: Vec<TaskGroup>,
-    pub config_path: PathBuf,
+    pub archive: Archive,
+    /// Queries saved by the user, by name
+    pub saved_queries: BTreeMap<String, String>,
+    /// The user's preferences
+    pub config: Config,
+    /// The preferences as they were loaded or last saved, so that the config file is only
+    /// written when they changed, and changes made elsewhere aren't reverted otherwise
+    saved_config: Config,
+    /// The folder holding the config file and the REPL's history.
+    /// None for lists that only live in memory, such as the ones in tests
+    pub config_path: Option<PathBuf>,
+    /// Where the list is loaded from and saved to
+    pub storage: SharedStorage,
+    /// How many errors were reported while evaluating commands
+    pub errors_reported: usize,
+    /// Whether the commands are only previewed, as with `--dry-run`, in which case
+    /// they don't write any file
+    pub dry_run: bool,
+    /// Summaries of the commands that changed the list or its configuration
+    /// since they were loaded or last saved
+    pub unsaved_changes: Vec<String>,
 }
 
 impl fmt::Display for TodoList {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for (i, group) in self.task_groups.iter().enumerate() {
-            writeln!(f, "{}. {}", i + 1, group)?;
+        write!(f, "{}", self.view(&DisplayOptions::default()))
+    }
+}
+
+/// A todo list along with the options on how to display it
+pub struct TodoListView<'a> {
+    list: &'a TodoList,
+    options: &'a DisplayOptions,
+}
+
+impl<'a> TodoListView<'a> {
+    /// The tasks of the given group that pass the filter (and aren't hidden for being done),
+    /// along with their zero-indexed positions
+    fn visible_tasks(&self, group_idx: usize, today: NaiveDate) -> Vec<(usize, &'a Task)> {
+        let group = &self.list.task_groups[group_idx];
+        let hide_done = self.options.hide_done.unwrap_or(false);
+        group
+            .tasks
+            .iter()
+            .enumerate()
+            .filter(|(_, task)| !(hide_done && task.is_done))
+            .filter(|(_, task)| match &self.options.filter {
+                Some(filter) => filter.matches(group_idx + 1, group, task, today),
+                None => true,
+            })
+            .collect()
+    }
+
+    /// The maximum amount of tasks to be shown per group, if there's one
+    fn limit(&self) -> Option<usize> {
+        self.options.limit.filter(|&limit| limit > 0)
+    }
+
+    /// Sorts the tasks to be shown, if a sorting order was requested.
+    /// The sort is stable, so tasks that compare equal keep their stored order.
+    fn sort<T>(&self, tasks: &mut [T], task_of: impl Fn(&T) -> &Task) {
+        if let Some(key) = self.options.sort {
+            tasks.sort_by(|left, right| {
+                let ordering = key.compare(task_of(left), task_of(right));
+                match self.options.reverse {
+                    true => ordering.reverse(),
+                    false => ordering,
+                }
+            });
+        }
+    }
+
+    fn fmt_flat(&self, f: &mut fmt::Formatter<'_>, today: NaiveDate) -> fmt::Result {
+        let mut tasks: Vec<(usize, usize, &Task)> = (0..self.list.task_groups.len())
+            .flat_map(|group_idx| {
+                self.visible_tasks(group_idx, today)
+                    .into_iter()
+                    .map(move |(task_idx, task)| (group_idx, task_idx, task))
+            })
+            .collect();
+        self.sort(&mut tasks, |(_, _, task)| task);
+
+        // There are no groups in a flat list, so the limit applies to the whole list
+        let hidden = match self.limit() {
+            Some(limit) if tasks.len() > limit => tasks.split_off(limit).len(),
+            _ => 0,
+        };
+
+        for (group_idx, task_idx, task) in tasks {
+            write!(f, "{}.{} ", group_idx + 1, task_idx + 1)?;
+            task.fmt_with(f, self.options)?;
+            writeln!(f, " ({})", self.list.task_groups[group_idx].name)?;
+        }
+
+        if hidden > 0 {
+            writeln!(f, "... and {} more", hidden)?;
         }
 
         write!(f, "")
     }
 }
 
-impl TryFrom<PathBuf> for TodoList {
-    type Error = errors::Error;
+impl fmt::Display for TodoListView<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let today = Local::now().date_naive();
 
-    fn try_from(config_path: PathBuf) -> Result<Self, Self::Error> {
-        let save_file_path = config_path.join("dodo.ron");
+        if self.options.flat {
+            return self.fmt_flat(f, today);
+        }
 
-        let task_groups: Vec<TaskGroup> = match save_file_path.exists() {
-            true => {
-                let file_contents = fs::read_to_string(save_file_path)?;
-                ron::de::from_str(&file_contents)?
+        for (i, group) in self.list.task_groups.iter().enumerate() {
+            let mut tasks = self.visible_tasks(i, today);
+            // Groups without any matching task are left out entirely
+            if tasks.is_empty() && self.options.filter.is_some() {
+                continue;
             }
-            false => {
-                println!(
-                    "{}: could not find a `dodo.ron` in `{:#?}`. \nCreating a new save file.",
-                    "warning".yellow(),
-                    save_file_path
-                );
-                vec![]
+
+            if self.options.collapse_done.unwrap_or(false) && group.is_completed() {
+                writeln!(f, "{}. {} (all done)\n", i + 1, group.name)?;
+                continue;
             }
-        };
 
-        Ok(Self {
-            task_groups,
-            config_path,
-        })
+            if self.options.counts_only.unwrap_or(false) {
+                let pending = tasks.iter().filter(|(_, task)| !task.is_done).count();
+                writeln!(f, "{}. {}: {} pending", i + 1, group.name, pending)?;
+                continue;
+            }
+
+            self.sort(&mut tasks, |(_, task)| task);
+            let hidden = match self.limit() {
+                Some(limit) if tasks.len() > limit => tasks.split_off(limit).len(),
+                _ => 0,
+            };
+
+            write!(f, "{}. ", i + 1)?;
+            group.fmt_tasks(f, tasks.into_iter(), self.options)?;
+            if hidden > 0 {
+                writeln!(f, "\t... and {} more", hidden)?;
+            }
+            writeln!(f)?;
+        }
+
+        write!(f, "")
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+/// What to do with a list that was changed elsewhere since it was loaded
+pub enum Resolution {
+    /// Drops the changes made to this list and loads the stored one
+    Reload,
+    /// Keeps the changes made in both places
+    Merge,
+    /// Replaces the stored list with this one
+    Overwrite,
+}
+
+/// Loads the list of the given config folder, from the storage its configuration selects
+impl TryFrom<PathBuf> for TodoList {
+    type Error = errors::Error;
+
+    fn try_from(config_path: PathBuf) -> Result<Self, Self::Error> {
+        let config = Config::load(&config_path)?;
+        let storage = storage::open(&config, &config_path)?;
+        Self::load(storage, config, Some(config_path))
     }
 }
 
+/// Creates a list that only lives in memory
 impl TryFrom<Vec<TaskGroup>> for TodoList {
     type Error = errors::Error;
 
     fn try_from(task_groups: Vec<TaskGroup>) -> Result<Self, Self::Error> {
-        let config_path = config_path::get_config_path()?;
-
-        Ok(Self {
+        let storage = SharedStorage::new(MemoryStorage::with_list(StoredList {
             task_groups,
-            config_path,
-        })
+            ..Default::default()
+        }));
+        Self::load(storage, Config::default(), None)
     }
 }
 
@@ -76,76 +210,592 @@ impl TodoList {
     //     let cfg_path = config_path::get_config_path();
     // }
 
-    pub fn save_to_file(&self) -> Result<(), Error> {
-        let serialized_data = ron::ser::to_string(&self.task_groups)?;
-        let save_file = File::create(self.config_path.join("dodo.ron"))?;
-        let mut writer = BufWriter::new(save_file);
-        writer.write_all(serialized_data.as_bytes())?;
+    /// Loads the list from the given storage
+    pub fn load(
+        storage: SharedStorage,
+        config: Config,
+        config_path: Option<PathBuf>,
+    ) -> Result<Self, Error> {
+        let StoredList {
+            task_groups,
+            archive,
+            saved_queries,
+        } = storage.load()?;
+
+        Ok(Self {
+            task_groups,
+            archive,
+            saved_queries,
+            saved_config: config.clone(),
+            config,
+            config_path,
+            storage,
+            errors_reported: 0,
+            dry_run: false,
+            unsaved_changes: vec![],
+        })
+    }
+
+    /// Loads only the configuration of the given config folder, along with an empty list,
+    /// for the commands that only deal with the configuration. That way, they can still fix
+    /// a configuration that keeps the list from loading, such as an unavailable storage
+    pub fn config_only(config_path: PathBuf) -> Result<Self, Error> {
+        let config = Config::load_unchecked(&config_path)?;
+        Self::load(SharedStorage::default(), config, Some(config_path))
+    }
+
+    /// Whether the list or its configuration changed since they were loaded or last saved
+    pub fn is_dirty(&self) -> bool {
+        !self.unsaved_changes.is_empty()
+    }
+
+    /// Replaces everything about the list that's stored with the given list
+    pub fn set_stored(&mut self, list: StoredList) {
+        self.task_groups = list.task_groups;
+        self.archive = list.archive;
+        self.saved_queries = list.saved_queries;
+    }
+
+    /// Returns a copy of everything about the list that's stored, apart from its configuration
+    pub fn stored(&self) -> StoredList {
+        StoredList {
+            task_groups: self.task_groups.clone(),
+            archive: self.archive.clone(),
+            saved_queries: self.saved_queries.clone(),
+        }
+    }
+
+    /// Saves the list to its storage, and its configuration to the config folder if it has one.
+    /// The changes are committed when the folder is synced with git
+    pub fn save(&mut self) -> Result<(), Error> {
+        self.storage.save(&self.stored())?;
+        if let Some(config_path) = &self.config_path {
+            if self.config != self.saved_config {
+                self.config.save(config_path)?;
+                self.saved_config = self.config.clone();
+            }
+            if self.config.sync.auto_commit && sync::is_repository(config_path) {
+                let message = sync::commit_message(&self.unsaved_changes);
+                if let Err(err) = sync::commit(config_path, &message) {
+                    println!("{}: {}", "warning".yellow(), err);
+                }
+            }
+        }
+        self.unsaved_changes.clear();
         Ok(())
     }
 
+    /// Loads the list from its storage again, along with its configuration
+    pub fn reload(&mut self) -> Result<(), Error> {
+        if let Some(config_path) = &self.config_path {
+            self.config = Config::load(config_path)?;
+            self.config.apply();
+            self.saved_config = self.config.clone();
+        }
+        let list = self.storage.load()?;
+        self.set_stored(list);
+        self.unsaved_changes.clear();
+        Ok(())
+    }
+
+    /// Deals with a list that couldn't be saved because it was changed elsewhere since it was loaded,
+    /// e.g. by a `dodo -d 1.2` run while a REPL was open
+    pub fn resolve(&mut self, resolution: Resolution) -> Result<(), Error> {
+        let base = self.storage.last_saved().unwrap_or_default();
+        let theirs = self.storage.load()?;
+
+        match resolution {
+            Resolution::Reload => {
+                self.set_stored(theirs);
+                self.unsaved_changes.clear();
+                println!("{}: reloaded the list.", "info".yellow());
+                return Ok(());
+            }
+            Resolution::Merge => {
+                let merge = merge::merge(&base, &self.stored(), &theirs);
+                self.set_stored(merge.list);
+                match merge.conflicts {
+                    0 => println!("{}: merged the changes made elsewhere.", "info".yellow()),
+                    conflicts => println!(
+                        "{}: merged the changes made elsewhere. {} task(s) were changed in both places, the changes made here were kept.",
+                        "info".yellow(),
+                        conflicts
+                    ),
+                }
+            }
+            Resolution::Overwrite => {}
+        }
+
+        self.save()
+    }
+
+    /// Returns a displayable view of this list using the given options
+    pub fn view<'a>(&'a self, options: &'a DisplayOptions) -> TodoListView<'a> {
+        TodoListView {
+            list: self,
+            options,
+        }
+    }
+
+    /// Prints the list using the given options, resolving any saved query its filter uses.
+    /// Returns false if the filter couldn't be resolved
+    pub fn show(&self, options: &DisplayOptions) -> bool {
+        let filter = match &options.filter {
+            Some(filter) => match filter.clone().resolve(&self.saved_queries) {
+                Ok(filter) => Some(filter),
+                Err(err) => {
+                    println!("{}: {}", "error".red(), err);
+                    return false;
+                }
+            },
+            None => None,
+        };
+
+        let options = DisplayOptions {
+            filter,
+            ..options.clone()
+        }
+        .with_defaults(&self.config);
+        print!("{}", self.view(&options));
+        true
+    }
+
+    fn save_query(&mut self, name: String, query: String) {
+        let words: Vec<&str> = query.split(' ').filter(|x| !x.is_empty()).collect();
+        let resolved = filter::parse(&words).and_then(|filter| {
+            let mut saved_queries = self.saved_queries.clone();
+            saved_queries.insert(name.clone(), query.clone());
+            filter.resolve(&saved_queries)
+        });
+
+        match resolved {
+            Ok(_) => {
+                self.saved_queries.insert(name, query);
+            }
+            Err(err) => {
+                println!("{}: {}. Ignoring command.", "error".red(), err);
+                self.errors_reported += 1;
+            }
+        }
+    }
+
+    fn list_queries(&self) {
+        if self.saved_queries.is_empty() {
+            println!("There are no saved queries.");
+        }
+        for (name, query) in &self.saved_queries {
+            println!("@{}: {}", name, query);
+        }
+    }
+
+    fn list_aliases(&self) {
+        if self.config.aliases.is_empty() && self.config.macros.is_empty() {
+            println!("There are no aliases or macros.");
+        }
+        for (name, expansion) in &self.config.aliases {
+            println!("{} = {}", name, expansion);
+        }
+        for (name, lines) in &self.config.macros {
+            println!("{} = {}", name, lines.join(" ; "));
+        }
+    }
+
+    /// Returns the format to convert to or from, guessing it from the file's extension if needed
+    fn conversion_format(
+        &mut self,
+        options: &ConversionOptions,
+        path: Option<&Path>,
+    ) -> Option<Format> {
+        let format = options.format.or_else(|| Format::from_path(path?));
+        if format.is_none() {
+            println!(
+                "{}: could not tell which format to use. Pass it with `--format` or `format:NAME`, NAME being one of: {}.",
+                "error".red(),
+                formats::FORMAT_NAMES.join(", ")
+            );
+            self.errors_reported += 1;
+        }
+        format
+    }
+
+    /// Writes the list in another format, to the given file or to stdout
+    fn export(&mut self, options: &ConversionOptions, path: Option<PathBuf>) {
+        let format = match self.conversion_format(options, path.as_deref()) {
+            Some(format) => format,
+            None => return,
+        };
+
+        // Calendar apps tell entries apart by UID, so tasks keep theirs from one export to the next
+        if format == Format::Ical {
+            for task in self
+                .task_groups
+                .iter_mut()
+                .flat_map(|group| group.tasks.iter_mut())
+                .filter(|task| task.due_date.is_some())
+            {
+                task.uid();
+            }
+        }
+
+        let contents = match formats::export(format, &self.task_groups, options) {
+            Ok(contents) => contents,
+            Err(err) => {
+                println!("{}: could not export the list: {}", "error".red(), err);
+                self.errors_reported += 1;
+                return;
+            }
+        };
+
+        match path {
+            None => print!("{}", contents),
+            Some(path) if self.dry_run => println!(
+                "{}: {} wasn't written, since this is a dry run.",
+                "info".yellow(),
+                path.display()
+            ),
+            Some(path) => match fs::write(&path, contents) {
+                Ok(()) => println!(
+                    "{}: exported the list to {}.",
+                    "info".yellow(),
+                    path.display()
+                ),
+                Err(err) => {
+                    println!(
+                        "{}: could not write {}: {}",
+                        "error".red(),
+                        path.display(),
+                        err
+                    );
+                    self.errors_reported += 1;
+                }
+            },
+        }
+    }
+
+    /// Reads the tasks of a file in another format, either merging them into the list
+    /// or replacing the list with them
+    fn import(&mut self, options: &ConversionOptions, path: &Path, replace: bool) {
+        let format = match self.conversion_format(options, Some(path)) {
+            Some(format) => format,
+            None => return,
+        };
+
+        let task_groups = fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|contents| formats::import(format, &contents, options));
+        let task_groups = match task_groups {
+            Ok(task_groups) => task_groups,
+            Err(err) => {
+                println!(
+                    "{}: could not import {}: {}",
+                    "error".red(),
+                    path.display(),
+                    err
+                );
+                self.errors_reported += 1;
+                return;
+            }
+        };
+
+        let imported: usize = task_groups.iter().map(|group| group.tasks.len()).sum();
+        let updated = match replace {
+            true => {
+                self.task_groups = task_groups;
+                0
+            }
+            false => self.merge(task_groups),
+        };
+        match updated {
+            0 => println!(
+                "{}: imported {} task(s) from {}.",
+                "info".yellow(),
+                imported,
+                path.display()
+            ),
+            updated => println!(
+                "{}: imported {} task(s) from {}, {} of which updated existing tasks.",
+                "info".yellow(),
+                imported,
+                path.display(),
+                updated
+            ),
+        }
+    }
+
+    /// Adds the tasks of the given groups to the groups of the same name,
+    /// creating the groups that don't exist yet. Tasks with the UID of a task already in the list
+    /// replace it instead. Returns how many tasks were replaced
+    fn merge(&mut self, task_groups: Vec<TaskGroup>) -> usize {
+        let mut updated = 0;
+
+        for mut group in task_groups {
+            let was_empty = group.tasks.is_empty();
+            group.tasks.retain(|task| {
+                let existing = task.uid.as_ref().and_then(|uid| {
+                    self.task_groups
+                        .iter_mut()
+                        .flat_map(|group| group.tasks.iter_mut())
+                        .find(|existing| existing.uid.as_ref() == Some(uid))
+                });
+                match existing {
+                    Some(existing) => {
+                        *existing = task.clone();
+                        updated += 1;
+                        false
+                    }
+                    None => true,
+                }
+            });
+
+            match self
+                .task_groups
+                .iter_mut()
+                .find(|existing| existing.name == group.name)
+            {
+                Some(existing) => existing.tasks.extend(group.tasks),
+                // Groups whose tasks all replaced existing ones aren't added
+                None if was_empty || !group.tasks.is_empty() => self.task_groups.push(group),
+                None => {}
+            }
+        }
+
+        updated
+    }
+
     fn add_task(&mut self, description: String, group_no: u16) {
-        let group_no = group_no as usize - 1;
         if let Some(group) = self.get_group_mut(group_no) {
-            let new_task = Task {
-                description,
-                is_done: false,
-            };
-            group.tasks.push(new_task)
+            match Task::from_input(&description) {
+                Ok(task) => group.tasks.push(task),
+                Err(err) => {
+                    println!("{}: {}. Ignoring command.", "error".red(), err);
+                    self.errors_reported += 1;
+                }
+            }
+        }
+    }
+
+    /// The group with the given number, counting from 1, reporting an error if there's none
+    fn get_group_mut(&mut self, group_no: u16) -> Option<&mut TaskGroup> {
+        let group_no = group_no as usize;
+        if group_no == 0 || group_no > self.task_groups.len() {
+            println!("{}: group no. {} not found.", "error".red(), group_no);
+            self.errors_reported += 1;
+            return None;
         }
+        Some(&mut self.task_groups[group_no - 1])
     }
 
-    fn get_group_mut(&mut self, group_no: usize) -> Option<&mut TaskGroup> {
-        match self.task_groups.get_mut(group_no as usize) {
-            Some(task_group) => Some(task_group),
-            None => {
-                println!("Bad argument for `group number`, ignoring command.");
-                None
+    /// Flips the task at the given zero-indexed position.
+    /// Completing a recurring task schedules its next occurrence at the end of its group,
+    /// and the recurrence moves on to it. Undoing the completion takes the occurrence back
+    /// as long as it's still pending, so that the task doesn't end up twice in the list.
+    /// Only tasks after the given position may be removed
+    fn flip_at(&mut self, group_idx: usize, task_idx: usize) {
+        let tasks = &mut self.task_groups[group_idx].tasks;
+        let completed_at = tasks[task_idx].completed_at;
+        let task = &mut tasks[task_idx];
+        task.flip();
+
+        if task.is_done {
+            if let Some(mut next) = task.next_occurrence() {
+                // The occurrence is created as the task is completed, which tells it apart later on
+                next.created_at = task.completed_at;
+                next.modified_at = task.completed_at;
+                task.recurrence = None;
+                tasks.push(next);
             }
+            return;
+        }
+
+        let description = &tasks[task_idx].description;
+        let scheduled = tasks[task_idx + 1..].iter().position(|next| {
+            completed_at.is_some()
+                && next.created_at == completed_at
+                && next.description == *description
+                && next.recurrence.is_some()
+                && !next.is_done
+        });
+        if let Some(offset) = scheduled {
+            let next = tasks.remove(task_idx + 1 + offset);
+            tasks[task_idx].recurrence = next.recurrence;
         }
     }
 
-    fn get_task_mut(&mut self, group_no: u16, task_no: u16) -> Option<&mut Task> {
-        let (task_no, group_no) = (task_no as usize - 1, group_no as usize - 1);
-        let task_group = match self.get_group_mut(group_no) {
-            Some(task_group_mut) => task_group_mut,
-            None => return None,
+    /// Resolves task references into zero-indexed (group, task) positions,
+    /// sorted and without repetitions.
+    /// Returns the references that couldn't be resolved if there are any.
+    fn resolve_task_refs(&self, refs: &[TaskRef]) -> Result<Vec<(usize, usize)>, Vec<TaskRef>> {
+        let mut positions = vec![];
+        let mut invalid = vec![];
+
+        let group_len = |group_no: u16| match group_no as usize {
+            0 => None,
+            group_no => self.task_groups.get(group_no - 1).map(|g| g.tasks.len()),
         };
 
-        match task_group.tasks.get_mut(task_no) {
-            Some(task) => Some(task),
-            None => {
-                println!("Bad argument for `task number`, ignoring command.");
-                None
+        for task_ref in refs {
+            let (group_no, first, last) = match *task_ref {
+                TaskRef::Single(group_no, task_no) => (group_no, task_no, task_no),
+                TaskRef::Range(group_no, first, last) => (group_no, first, last),
+                TaskRef::WholeGroup(group_no) => match group_len(group_no) {
+                    Some(len) => {
+                        let group_idx = group_no as usize - 1;
+                        positions.extend((0..len).map(|task_idx| (group_idx, task_idx)));
+                        continue;
+                    }
+                    None => {
+                        invalid.push(task_ref.clone());
+                        continue;
+                    }
+                },
+            };
+
+            match group_len(group_no) {
+                Some(len) if first >= 1 && last as usize <= len => {
+                    let group_idx = group_no as usize - 1;
+                    positions
+                        .extend((first..=last).map(|task_no| (group_idx, task_no as usize - 1)));
+                }
+                _ => invalid.push(task_ref.clone()),
             }
         }
+
+        if !invalid.is_empty() {
+            return Err(invalid);
+        }
+
+        positions.sort_unstable();
+        positions.dedup();
+        Ok(positions)
     }
 
-    fn flip_task(&mut self, group_no: u16, task_no: u16) {
-        if let Some(task) = self.get_task_mut(group_no, task_no) {
-            task.is_done = !task.is_done;
+    fn report_invalid_refs(&mut self, invalid: &[TaskRef]) {
+        let invalid: Vec<String> = invalid.iter().map(ToString::to_string).collect();
+        println!(
+            "{}: invalid task references: {}. Ignoring command.",
+            "error".red(),
+            invalid.join(", ")
+        );
+        self.errors_reported += 1;
+    }
+
+    fn flip_tasks(&mut self, refs: &[TaskRef]) {
+        match self.resolve_task_refs(refs) {
+            Ok(positions) => {
+                // Flipping back to front keeps the remaining indices valid, should an occurrence
+                // of a recurring task be taken back
+                for (group_idx, task_idx) in positions.into_iter().rev() {
+                    self.flip_at(group_idx, task_idx);
+                }
+            }
+            Err(invalid) => self.report_invalid_refs(&invalid),
         }
     }
 
-    fn remove_task(&mut self, group_no: u16, task_no: u16) {
-        let (task_no, group_no) = (task_no as usize - 1, group_no as usize - 1);
-        if let Some(group) = self.get_group_mut(group_no) {
-            if task_no < group.tasks.len() {
-                group.tasks.remove(task_no);
+    fn remove_tasks(&mut self, refs: &[TaskRef]) {
+        match self.resolve_task_refs(refs) {
+            Ok(positions) => {
+                // Removing back to front keeps the remaining indices valid
+                for (group_idx, task_idx) in positions.into_iter().rev() {
+                    self.task_groups[group_idx].tasks.remove(task_idx);
+                }
+            }
+            Err(invalid) => self.report_invalid_refs(&invalid),
+        }
+    }
+
+    /// Moves the done tasks of one group (or of every group) into the archive
+    fn clean(&mut self, group_no: Option<u16>) {
+        let group_range = match group_no {
+            Some(group_no) => {
+                let group_no = group_no as usize;
+                if group_no == 0 || group_no > self.task_groups.len() {
+                    println!("{}: group no. {} not found.", "error".red(), group_no);
+                    self.errors_reported += 1;
+                    return;
+                }
+                group_no - 1..group_no
             }
+            None => 0..self.task_groups.len(),
+        };
+
+        let now = Local::now();
+        let mut archived = 0;
+        for group in &mut self.task_groups[group_range] {
+            let (done, pending): (Vec<Task>, Vec<Task>) =
+                group.tasks.drain(..).partition(|task| task.is_done);
+            group.tasks = pending;
+            archived += done.len();
+
+            self.archive
+                .entries
+                .extend(done.into_iter().map(|task| ArchivedTask {
+                    group_name: group.name.clone(),
+                    completed_at: task.completed_at.unwrap_or(now),
+                    task,
+                }));
+        }
+
+        println!("{}: archived {} task(s).", "info".yellow(), archived);
+    }
+
+    /// Moves an archived task back to the group it came from,
+    /// recreating the group if it no longer exists
+    fn restore(&mut self, entry_no: u16) {
+        let entry_idx = entry_no as usize;
+        if entry_idx == 0 || entry_idx > self.archive.entries.len() {
+            println!(
+                "{}: archive entry no. {} not found.",
+                "error".red(),
+                entry_no
+            );
+            self.errors_reported += 1;
+            return;
+        }
+
+        let ArchivedTask {
+            group_name, task, ..
+        } = self.archive.entries.remove(entry_idx - 1);
+
+        match self
+            .task_groups
+            .iter_mut()
+            .find(|group| group.name == group_name)
+        {
+            Some(group) => group.tasks.push(task),
+            None => self.task_groups.push(TaskGroup {
+                name: group_name,
+                tasks: vec![task],
+            }),
         }
     }
 
     fn remove_group(&mut self, group_no: u16) {
-        let group_no = group_no as usize - 1;
-        if group_no < self.task_groups.len() {
-            self.task_groups.remove(group_no);
-        } else {
-            println!("{}: group no. {} not found.", "error".red(), group_no + 1);
+        let group_no = group_no as usize;
+        if group_no == 0 || group_no > self.task_groups.len() {
+            println!("{}: group no. {} not found.", "error".red(), group_no);
+            self.errors_reported += 1;
+            return;
         }
+        self.task_groups.remove(group_no - 1);
     }
 
+    /// Runs the given command, keeping track of whether it changed the list or its configuration
     pub fn evaluate(&mut self, command: Command) {
+        // Nothing needs to be compared for commands that can't change anything
+        if command.is_read_only() {
+            return self.run(command);
+        }
+
+        let summary = command.summary();
+        let (list, config) = (self.stored(), self.config.clone());
+        self.run(command);
+        if self.stored() != list || self.config != config {
+            self.unsaved_changes.push(summary);
+        }
+    }
+
+    fn run(&mut self, command: Command) {
         use Command::*;
         match command {
             NoOp => {}
@@ -157,16 +807,115 @@ impl TodoList {
                 tasks: vec![],
             }),
             Command::FlipTask(group_no, task_no) => {
-                self.flip_task(group_no, task_no);
+                self.flip_tasks(&[TaskRef::Single(group_no, task_no)]);
+            }
+            Command::FlipTasks(refs) => {
+                self.flip_tasks(&refs);
             }
             Command::RemoveTask(group_no, task_no) => {
-                self.remove_task(group_no, task_no);
+                self.remove_tasks(&[TaskRef::Single(group_no, task_no)]);
+            }
+            Command::RemoveTasks(refs) => {
+                self.remove_tasks(&refs);
             }
             Command::RemoveGroup(group_no) => {
                 self.remove_group(group_no);
             }
-            Command::ShowList => {
-                print!("{}", self);
+            Command::ShowList(options) => {
+                if !self.show(&options) {
+                    self.errors_reported += 1;
+                }
+            }
+            Command::ShowStats => {
+                print!("{}", Statistics::compute(self, Local::now().date_naive()));
+            }
+            Command::SetDisplaySettings(options) => {
+                self.config.display.update(&options);
+            }
+            Command::ShowDisplaySettings => {
+                print!("{}", self.config.display);
+            }
+            Command::ConfigGet(key) => match self.config.get(&key) {
+                Ok(value) => println!("{}", value),
+                Err(err) => {
+                    println!("{}: {}", "error".red(), err);
+                    self.errors_reported += 1;
+                }
+            },
+            Command::ConfigSet(key, value) => match self.config.set(&key, &value) {
+                Ok(()) => self.config.apply(),
+                Err(err) => {
+                    println!("{}: {}", "error".red(), err);
+                    self.errors_reported += 1;
+                }
+            },
+            Command::ConfigList => {
+                print!("{}", self.config);
+            }
+            Command::SetAlias(name, expansion) => {
+                if let Err(err) = self.config.set_alias(name, expansion) {
+                    println!("{}: {}. Ignoring command.", "error".red(), err);
+                    self.errors_reported += 1;
+                }
+            }
+            Command::SetMacro(name, lines) => {
+                if let Err(err) = self.config.set_macro(name, lines) {
+                    println!("{}: {}. Ignoring command.", "error".red(), err);
+                    self.errors_reported += 1;
+                }
+            }
+            Command::RemoveAlias(name) => {
+                if !self.config.remove_alias(&name) {
+                    println!("{}: alias or macro `{}` not found.", "error".red(), name);
+                    self.errors_reported += 1;
+                }
+            }
+            Command::ListAliases => {
+                self.list_aliases();
+            }
+            Command::SaveQuery(name, query) => {
+                self.save_query(name, query);
+            }
+            Command::RemoveQuery(name) => {
+                if self.saved_queries.remove(&name).is_none() {
+                    println!("{}: query @{} not found.", "error".red(), name);
+                    self.errors_reported += 1;
+                }
+            }
+            Command::ListQueries => {
+                self.list_queries();
+            }
+            Command::Find(query) => match query.run(&self.task_groups) {
+                Ok(matches) if matches.is_empty() => println!("No tasks found."),
+                Ok(matches) => matches.iter().for_each(|found| println!("{}", found)),
+                Err(err) => {
+                    println!("{}: {}", "error".red(), err);
+                    self.errors_reported += 1;
+                }
+            },
+            Command::Clean(group_no) => {
+                self.clean(group_no);
+            }
+            Command::ShowArchive => {
+                print!("{}", self.archive.view(&self.config.date_format));
+            }
+            Command::Export(options, path) => {
+                self.export(&options, path);
+            }
+            Command::Import(options, path, replace) => {
+                self.import(&options, &path, replace);
+            }
+            Command::Restore(entry_no) => {
+                self.restore(entry_no);
+            }
+            // These are about the REPL's session, which handles them itself. Scripts and dry runs
+            // are saved once they're done, if at all
+            Command::Save | Command::Reload(_) | Command::Quit(_) => {
+                println!(
+                    "{}: `save`, `reload` and `quit` only work in the REPL.",
+                    "error".red()
+                );
+                self.errors_reported += 1;
             }
         }
     }
@@ -175,6 +924,21 @@ impl TodoList {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::display::{DisplaySettings, SortKey};
+    use chrono::TimeZone;
+
+    /// Timestamps depend on when the test runs, so they're cleared before comparisons
+    fn clear_timestamps(todo_list: &mut TodoList) {
+        for task in todo_list
+            .task_groups
+            .iter_mut()
+            .flat_map(|g| g.tasks.iter_mut())
+        {
+            task.created_at = None;
+            task.completed_at = None;
+            task.modified_at = None;
+        }
+    }
 
     fn sample_task_groups() -> Vec<TaskGroup> {
         vec![
@@ -183,6 +947,7 @@ mod test {
                 tasks: vec![Task {
                     description: "Study for the Physics test".into(),
                     is_done: true,
+                    ..Default::default()
                 }],
             },
             TaskGroup {
@@ -190,6 +955,7 @@ mod test {
                 tasks: vec![Task {
                     description: "Study for the Maths test".into(),
                     is_done: true,
+                    ..Default::default()
                 }],
             },
         ]
@@ -200,10 +966,11 @@ mod test {
         let mut todo_list = TodoList::try_from(sample_task_groups())?;
 
         let flip_task = Command::FlipTask(1, 1);
-        let config_path = config_path::get_config_path()?;
 
         todo_list.evaluate(flip_task);
 
+        clear_timestamps(&mut todo_list);
+
         assert_eq!(
             TodoList {
                 task_groups: vec![
@@ -212,6 +979,7 @@ mod test {
                         tasks: vec![Task {
                             description: "Study for the Physics test".into(),
                             is_done: false, // Flipped from true to false
+                            ..Default::default()
                         },],
                     },
                     TaskGroup {
@@ -219,10 +987,19 @@ mod test {
                         tasks: vec![Task {
                             description: "Study for the Maths test".into(),
                             is_done: true,
+                            ..Default::default()
                         },],
                     },
                 ],
-                config_path,
+                archive: Archive::default(),
+                saved_queries: BTreeMap::new(),
+                config: Config::default(),
+                saved_config: Config::default(),
+                config_path: None,
+                storage: SharedStorage::default(),
+                errors_reported: 0,
+                dry_run: false,
+                unsaved_changes: vec!["Flip task 1.1".into()],
             },
             todo_list
         );
@@ -236,11 +1013,12 @@ mod test {
 
         let add_task = Command::AddTask("New task".into(), 1);
         let flip_task = Command::FlipTask(1, 2);
-        let config_path = config_path::get_config_path()?;
 
         todo_list.evaluate(add_task);
         todo_list.evaluate(flip_task);
 
+        clear_timestamps(&mut todo_list);
+
         assert_eq!(
             TodoList {
                 task_groups: vec![
@@ -250,10 +1028,12 @@ mod test {
                             Task {
                                 description: "Study for the Physics test".into(),
                                 is_done: true,
+                                ..Default::default()
                             },
                             Task {
                                 description: "New task".into(),
                                 is_done: true,
+                                ..Default::default()
                             }
                         ],
                     },
@@ -262,10 +1042,19 @@ mod test {
                         tasks: vec![Task {
                             description: "Study for the Maths test".into(),
                             is_done: true,
+                            ..Default::default()
                         },],
                     },
                 ],
-                config_path,
+                archive: Archive::default(),
+                saved_queries: BTreeMap::new(),
+                config: Config::default(),
+                saved_config: Config::default(),
+                config_path: None,
+                storage: SharedStorage::default(),
+                errors_reported: 0,
+                dry_run: false,
+                unsaved_changes: vec!["Add `New task` to group 1".into(), "Flip task 1.2".into()],
             },
             todo_list
         );
@@ -279,8 +1068,6 @@ mod test {
         let add_group_cmd = Command::AddGroup("New group".into());
         todo_list.evaluate(add_group_cmd);
 
-        let config_path = config_path::get_config_path()?;
-
         assert_eq!(
             TodoList {
                 task_groups: vec![
@@ -289,6 +1076,7 @@ mod test {
                         tasks: vec![Task {
                             description: "Study for the Physics test".into(),
                             is_done: true,
+                            ..Default::default()
                         }],
                     },
                     TaskGroup {
@@ -296,6 +1084,7 @@ mod test {
                         tasks: vec![Task {
                             description: "Study for the Maths test".into(),
                             is_done: true,
+                            ..Default::default()
                         },],
                     },
                     TaskGroup {
@@ -303,7 +1092,15 @@ mod test {
                         tasks: vec![],
                     }
                 ],
-                config_path
+                archive: Archive::default(),
+                saved_queries: BTreeMap::new(),
+                config: Config::default(),
+                saved_config: Config::default(),
+                config_path: None,
+                storage: SharedStorage::default(),
+                errors_reported: 0,
+                dry_run: false,
+                unsaved_changes: vec!["Add group `New group`".into()],
             },
             todo_list
         );
@@ -316,10 +1113,11 @@ mod test {
         let mut todo_list = TodoList::try_from(sample_task_groups())?;
 
         let add_new_task = Command::AddTask("Sample new task".into(), 1);
-        let config_path = config_path::get_config_path()?;
 
         todo_list.evaluate(add_new_task);
 
+        clear_timestamps(&mut todo_list);
+
         assert_eq!(
             TodoList {
                 task_groups: vec![
@@ -329,10 +1127,12 @@ mod test {
                             Task {
                                 description: "Study for the Physics test".into(),
                                 is_done: true,
+                                ..Default::default()
                             },
                             Task {
                                 description: "Sample new task".into(),
                                 is_done: false,
+                                ..Default::default()
                             }
                         ],
                     },
@@ -341,13 +1141,476 @@ mod test {
                         tasks: vec![Task {
                             description: "Study for the Maths test".into(),
                             is_done: true,
+                            ..Default::default()
                         },],
                     },
                 ],
-                config_path
+                archive: Archive::default(),
+                saved_queries: BTreeMap::new(),
+                config: Config::default(),
+                saved_config: Config::default(),
+                config_path: None,
+                storage: SharedStorage::default(),
+                errors_reported: 0,
+                dry_run: false,
+                unsaved_changes: vec!["Add `Sample new task` to group 1".into()],
             },
             todo_list
         );
         Ok(())
     }
+
+    #[test]
+    fn flip_task_range() -> Result<(), errors::Error> {
+        let mut todo_list = TodoList::try_from(sample_task_groups())?;
+        todo_list.evaluate(Command::AddTask("New task".into(), 1));
+        todo_list.evaluate(Command::FlipTasks(vec![
+            TaskRef::Range(1, 1, 2),
+            TaskRef::WholeGroup(2),
+        ]));
+
+        let is_done: Vec<bool> = todo_list
+            .task_groups
+            .iter()
+            .flat_map(|group| group.tasks.iter().map(|task| task.is_done))
+            .collect();
+        assert_eq!(is_done, vec![false, true, false]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn bulk_operations_are_atomic() -> Result<(), errors::Error> {
+        let mut todo_list = TodoList::try_from(sample_task_groups())?;
+        // 1.2 does not exist, so nothing should change
+        todo_list.evaluate(Command::FlipTasks(vec![
+            TaskRef::Single(1, 1),
+            TaskRef::Single(1, 2),
+        ]));
+        todo_list.evaluate(Command::RemoveTasks(vec![
+            TaskRef::WholeGroup(2),
+            TaskRef::WholeGroup(3),
+        ]));
+
+        assert_eq!(todo_list.task_groups, sample_task_groups());
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_single_refs() -> Result<(), errors::Error> {
+        let mut todo_list = TodoList::try_from(sample_task_groups())?;
+        todo_list.evaluate(Command::FlipTask(1, 0));
+        todo_list.evaluate(Command::FlipTask(1, 2));
+        todo_list.evaluate(Command::RemoveTask(1, 0));
+        todo_list.evaluate(Command::RemoveTask(3, 1));
+
+        assert_eq!(todo_list.errors_reported, 4);
+        assert_eq!(todo_list.task_groups, sample_task_groups());
+
+        Ok(())
+    }
+
+    #[test]
+    fn remove_task_list() -> Result<(), errors::Error> {
+        let mut todo_list = TodoList::try_from(sample_task_groups())?;
+        todo_list.evaluate(Command::AddTask("Second".into(), 1));
+        todo_list.evaluate(Command::AddTask("Third".into(), 1));
+        todo_list.evaluate(Command::RemoveTasks(vec![
+            TaskRef::Single(1, 3),
+            TaskRef::Single(1, 1),
+            TaskRef::Single(1, 1),
+        ]));
+
+        let descriptions: Vec<&str> = todo_list.task_groups[0]
+            .tasks
+            .iter()
+            .map(|task| task.description.as_str())
+            .collect();
+        assert_eq!(descriptions, vec!["Second"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn clean_and_restore() -> Result<(), errors::Error> {
+        let mut todo_list = TodoList::try_from(sample_task_groups())?;
+        todo_list.evaluate(Command::AddTask("Pending task".into(), 1));
+        todo_list.evaluate(Command::Clean(Some(0)));
+        todo_list.evaluate(Command::Clean(Some(3)));
+        assert_eq!(todo_list.errors_reported, 2);
+        assert!(todo_list.archive.entries.is_empty());
+
+        todo_list.evaluate(Command::Clean(Some(1)));
+        assert_eq!(todo_list.task_groups[0].tasks.len(), 1);
+        assert_eq!(todo_list.task_groups[1].tasks.len(), 1);
+        assert_eq!(todo_list.archive.entries.len(), 1);
+        assert_eq!(todo_list.archive.entries[0].group_name, "Group 1");
+
+        todo_list.evaluate(Command::RemoveGroup(1));
+        todo_list.evaluate(Command::Restore(1));
+
+        assert!(todo_list.archive.entries.is_empty());
+        assert_eq!(todo_list.task_groups[1].name, "Group 1");
+        assert_eq!(
+            todo_list.task_groups[1].tasks,
+            vec![Task {
+                description: "Study for the Physics test".into(),
+                is_done: true,
+                ..Default::default()
+            }]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn timestamps() -> Result<(), errors::Error> {
+        let mut todo_list = TodoList::try_from(sample_task_groups())?;
+        todo_list.evaluate(Command::AddTask("New task".into(), 2));
+
+        let task = &todo_list.task_groups[1].tasks[1];
+        assert!(task.created_at.is_some());
+        assert_eq!(task.created_at, task.modified_at);
+        assert_eq!(task.completed_at, None);
+
+        todo_list.evaluate(Command::FlipTask(2, 2));
+        let task = &todo_list.task_groups[1].tasks[1];
+        assert!(task.completed_at.is_some());
+        assert_eq!(task.completed_at, task.modified_at);
+
+        todo_list.evaluate(Command::FlipTask(2, 2));
+        let task = &todo_list.task_groups[1].tasks[1];
+        assert_eq!(task.completed_at, None);
+        assert!(task.modified_at >= task.created_at);
+
+        Ok(())
+    }
+
+    #[test]
+    fn completing_recurring_task() -> Result<(), errors::Error> {
+        let mut todo_list = TodoList::try_from(sample_task_groups())?;
+        todo_list.evaluate(Command::AddTask("Water plants every:3d".into(), 2));
+        todo_list.evaluate(Command::FlipTask(2, 2));
+
+        let tasks = &todo_list.task_groups[1].tasks;
+        assert_eq!(tasks.len(), 3);
+        assert!(tasks[1].is_done);
+
+        let next = &tasks[2];
+        assert!(!next.is_done);
+        assert_eq!(next.description, "Water plants");
+        assert_eq!(next.recurrence, Some("3d".parse().unwrap()));
+        assert_eq!(tasks[1].recurrence, None);
+        assert!(next.due_date > Some(Local::now().date_naive()));
+
+        // Undoing the completion takes back the occurrence it scheduled
+        todo_list.evaluate(Command::FlipTask(2, 2));
+        let tasks = &todo_list.task_groups[1].tasks;
+        assert_eq!(tasks.len(), 2);
+        assert!(!tasks[1].is_done);
+        assert_eq!(tasks[1].recurrence, Some("3d".parse().unwrap()));
+
+        // Redoing it schedules a single occurrence again
+        todo_list.evaluate(Command::FlipTask(2, 2));
+        assert_eq!(todo_list.task_groups[1].tasks.len(), 3);
+
+        // Once the occurrence is done too, undoing the first completion leaves it alone
+        todo_list.evaluate(Command::FlipTask(2, 3));
+        todo_list.evaluate(Command::FlipTask(2, 2));
+        let tasks = &todo_list.task_groups[1].tasks;
+        assert_eq!(tasks.len(), 4);
+        assert_eq!(tasks[1].recurrence, None);
+        assert!(tasks[2].is_done);
+        assert!(!tasks[3].is_done);
+
+        Ok(())
+    }
+
+    #[test]
+    fn dates_without_a_usable_format() -> Result<(), errors::Error> {
+        let mut todo_list = TodoList::try_from(sample_task_groups())?;
+        todo_list.evaluate(Command::AddTask("Buy milk due:2021-03-02".into(), 1));
+
+        // A date can't be written with a time format, so the default one is used instead
+        let options = DisplayOptions {
+            date_format: Some("%H:%M".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            todo_list.view(&options).to_string(),
+            "1. Group 1 [1/2] █████░░░░░ 50%\n\t1. [x] - Study for the Physics test\n\t2. [ ] - Buy milk (due 2021-03-02)\n\n\
+             2. College [1/1] ██████████ 100%\n\t1. [x] - Study for the Maths test\n\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn showing_timestamps() -> Result<(), errors::Error> {
+        let created = Local.with_ymd_and_hms(2021, 3, 1, 9, 0, 0).unwrap();
+        let modified = Local.with_ymd_and_hms(2021, 3, 2, 9, 0, 0).unwrap();
+        let mut task_groups = sample_task_groups();
+        task_groups[0].tasks = vec![
+            Task {
+                description: "Buy milk".into(),
+                created_at: Some(created),
+                modified_at: Some(created),
+                ..Default::default()
+            },
+            Task {
+                description: "Answer emails".into(),
+                is_done: true,
+                created_at: Some(created),
+                completed_at: Some(modified),
+                modified_at: Some(modified),
+                ..Default::default()
+            },
+        ];
+        let todo_list = TodoList::try_from(task_groups)?;
+
+        let options = DisplayOptions {
+            show_timestamps: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            todo_list.view(&options).to_string(),
+            "1. Group 1 [1/2] █████░░░░░ 50%\n\t1. [ ] - Buy milk (created 2021-03-01)\n\
+             \t2. [x] - Answer emails (created 2021-03-01, done 2021-03-02, modified 2021-03-02)\n\n\
+             2. College [1/1] ██████████ 100%\n\t1. [x] - Study for the Maths test (created unknown, done unknown)\n\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn sorted_views_keep_task_numbers() -> Result<(), errors::Error> {
+        let mut todo_list = TodoList::try_from(sample_task_groups())?;
+        todo_list.evaluate(Command::AddTask("Buy milk due:2021-03-02".into(), 1));
+        todo_list.evaluate(Command::AddTask("Answer emails due:2021-03-01".into(), 2));
+
+        let options = DisplayOptions {
+            sort: Some(SortKey::Due),
+            ..Default::default()
+        };
+        assert_eq!(
+            todo_list.view(&options).to_string(),
+            "1. Group 1 [1/2] █████░░░░░ 50%\n\t2. [ ] - Buy milk (due 2021-03-02)\n\t1. [x] - Study for the Physics test\n\n\
+             2. College [1/2] █████░░░░░ 50%\n\t2. [ ] - Answer emails (due 2021-03-01)\n\t1. [x] - Study for the Maths test\n\n"
+        );
+
+        let options = DisplayOptions {
+            sort: Some(SortKey::Alphabetical),
+            flat: true,
+            ..options
+        };
+        assert_eq!(
+            todo_list.view(&options).to_string(),
+            "2.2 [ ] - Answer emails (due 2021-03-01) (College)\n\
+             1.2 [ ] - Buy milk (due 2021-03-02) (Group 1)\n\
+             2.1 [x] - Study for the Maths test (College)\n\
+             1.1 [x] - Study for the Physics test (Group 1)\n"
+        );
+
+        // The stored order is left untouched
+        assert_eq!(
+            todo_list.task_groups[0].tasks[0].description,
+            "Study for the Physics test"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn compact_views() -> Result<(), errors::Error> {
+        let mut todo_list = TodoList::try_from(sample_task_groups())?;
+        todo_list.evaluate(Command::AddTask("Buy milk".into(), 1));
+        todo_list.evaluate(Command::AddTask("Buy bread".into(), 1));
+
+        let options = DisplayOptions {
+            hide_done: Some(true),
+            collapse_done: Some(true),
+            limit: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            todo_list.view(&options).to_string(),
+            "1. Group 1 [1/3] ███░░░░░░░ 33%\n\t2. [ ] - Buy milk\n\t... and 1 more\n\n2. College (all done)\n\n"
+        );
+
+        let options = DisplayOptions {
+            counts_only: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(
+            todo_list.view(&options).to_string(),
+            "1. Group 1: 2 pending\n2. College: 0 pending\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn display_settings_are_defaults() -> Result<(), errors::Error> {
+        let mut todo_list = TodoList::try_from(sample_task_groups())?;
+        todo_list.evaluate(Command::SetDisplaySettings(DisplayOptions {
+            hide_done: Some(true),
+            limit: Some(5),
+            ..Default::default()
+        }));
+
+        let options = DisplayOptions {
+            hide_done: Some(false),
+            ..Default::default()
+        }
+        .with_defaults(&todo_list.config);
+        assert_eq!(options.hide_done, Some(false));
+        assert_eq!(options.limit, Some(5));
+
+        todo_list.evaluate(Command::SetDisplaySettings(DisplayOptions {
+            limit: Some(0),
+            ..Default::default()
+        }));
+        assert_eq!(
+            todo_list.config.display,
+            DisplaySettings {
+                hide_done: true,
+                ..Default::default()
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn merging_updates_tasks_by_uid() -> Result<(), errors::Error> {
+        let mut todo_list = TodoList::try_from(sample_task_groups())?;
+        let uid = todo_list.task_groups[1].tasks[0].uid().to_string();
+
+        let updated = todo_list.merge(vec![
+            TaskGroup {
+                name: "Calendar".into(),
+                tasks: vec![Task {
+                    description: "Study for the Maths exam".into(),
+                    uid: Some(uid),
+                    ..Default::default()
+                }],
+            },
+            TaskGroup {
+                name: "College".into(),
+                tasks: vec![Task {
+                    description: "Hand in the essay".into(),
+                    ..Default::default()
+                }],
+            },
+        ]);
+
+        assert_eq!(updated, 1);
+        let descriptions: Vec<Vec<&str>> = todo_list
+            .task_groups
+            .iter()
+            .map(|group| {
+                group
+                    .tasks
+                    .iter()
+                    .map(|task| task.description.as_str())
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            descriptions,
+            vec![
+                vec!["Study for the Physics test"],
+                vec!["Study for the Maths exam", "Hand in the essay"],
+            ]
+        );
+
+        Ok(())
+    }
+
+    /// Changes the prompt from another list while this one changes the list, then the prompt.
+    /// Returns the prompt saved after each step
+    fn edit_config_in_two_places(folder: PathBuf) -> Result<(String, String), errors::Error> {
+        let mut ours = TodoList::try_from(folder.clone())?;
+        let mut theirs = TodoList::try_from(folder.clone())?;
+
+        theirs.evaluate(Command::ConfigSet("prompt".into(), "$".into()));
+        theirs.save()?;
+        ours.evaluate(Command::AddGroup("Home".into()));
+        match ours.save() {
+            Err(Error::ExternalChanges) => ours.resolve(Resolution::Merge)?,
+            result => result?,
+        }
+        let kept = Config::load(&folder)?.prompt;
+
+        ours.evaluate(Command::ConfigSet("prompt".into(), "%".into()));
+        ours.save()?;
+        Ok((kept, Config::load(&folder)?.prompt))
+    }
+
+    #[test]
+    fn saving_keeps_config_changes_made_elsewhere() -> Result<(), errors::Error> {
+        let folder = std::env::temp_dir().join(format!("dodo-config-saves-{}", std::process::id()));
+        std::fs::create_dir_all(&folder)?;
+        let result = edit_config_in_two_places(folder.clone());
+        std::fs::remove_dir_all(&folder)?;
+
+        assert_eq!(result?, ("$".into(), "%".into()));
+        Ok(())
+    }
+
+    #[test]
+    fn tracking_unsaved_changes() -> Result<(), errors::Error> {
+        let mut todo_list = TodoList::try_from(sample_task_groups())?;
+
+        todo_list.evaluate(Command::ShowArchive);
+        assert!(!todo_list.is_dirty());
+        todo_list.evaluate(Command::AddGroup("Home".into()));
+        assert!(todo_list.is_dirty());
+        todo_list.save()?;
+        assert!(!todo_list.is_dirty());
+
+        todo_list.evaluate(Command::AddTask("Cook".into(), 3));
+        todo_list.reload()?;
+        assert!(!todo_list.is_dirty());
+        assert!(todo_list.task_groups[2].tasks.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn session_commands_outside_the_repl() -> Result<(), errors::Error> {
+        let mut todo_list = TodoList::try_from(sample_task_groups())?;
+        todo_list.evaluate(Command::AddGroup("Home".into()));
+
+        todo_list.evaluate(Command::Save);
+        todo_list.evaluate(Command::Reload(true));
+        assert_eq!(todo_list.errors_reported, 2);
+        assert!(todo_list.is_dirty());
+        assert_eq!(
+            todo_list.storage.last_saved(),
+            Some(StoredList {
+                task_groups: sample_task_groups(),
+                ..Default::default()
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn exporting_in_a_dry_run() -> Result<(), errors::Error> {
+        let mut todo_list = TodoList::try_from(sample_task_groups())?;
+        todo_list.dry_run = true;
+
+        let path = std::env::temp_dir().join(format!("dodo-dry-export-{}.txt", std::process::id()));
+        todo_list.evaluate(Command::Export(
+            ConversionOptions::default(),
+            Some(path.clone()),
+        ));
+        assert!(!path.exists());
+        assert_eq!(todo_list.errors_reported, 0);
+
+        Ok(())
+    }
 }
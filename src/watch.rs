@@ -0,0 +1,132 @@
+use std::{
+    path::Path,
+    sync::mpsc::{channel, Receiver},
+    time::Duration,
+};
+
+use chrono::Local;
+use colored::Colorize;
+use notify::{DebouncedEvent, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::display::DisplayOptions;
+use crate::errors::Error;
+use crate::todolist::TodoList;
+
+/// How long to wait for a burst of changes to end, e.g. the files of a save, before showing the list
+const DEBOUNCE_DELAY: Duration = Duration::from_millis(200);
+/// How often the files are checked when filesystem notifications aren't available
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Starts watching the given folder, sending events to the returned receiver
+/// for as long as the returned watcher is kept
+fn start<W: Watcher>(
+    folder: &Path,
+    delay: Duration,
+) -> notify::Result<(W, Receiver<DebouncedEvent>)> {
+    let (sender, receiver) = channel();
+    let mut watcher = W::new(sender, delay)?;
+    watcher.watch(folder, RecursiveMode::NonRecursive)?;
+    Ok((watcher, receiver))
+}
+
+/// Whether the file holds part of the list or its configuration, such as `dodo.ron` or `dodo.db`.
+/// Other files, such as the lock file taken while loading, are left alone
+fn is_list_file(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|extension| extension.to_str()),
+        Some("ron") | Some("db")
+    )
+}
+
+/// Clears the terminal and shows the list
+fn render(list: &TodoList, options: &DisplayOptions) {
+    print!("\x1b[2J\x1b[H");
+    list.show(options);
+    println!(
+        "{}",
+        format!(
+            "Updated at {}. Press Ctrl-C to stop watching.",
+            Local::now().format("%H:%M:%S")
+        )
+        .dimmed()
+    );
+}
+
+/// Shows the list again whenever it changes, until the events stop
+fn follow(list: &mut TodoList, options: &DisplayOptions, events: Receiver<DebouncedEvent>) {
+    for event in events {
+        let changed = match event {
+            DebouncedEvent::Create(path)
+            | DebouncedEvent::Write(path)
+            | DebouncedEvent::Remove(path)
+            | DebouncedEvent::Rename(_, path) => is_list_file(&path),
+            DebouncedEvent::Rescan => true,
+            DebouncedEvent::Error(err, _) => {
+                println!("{}: {}", "error".red(), err);
+                false
+            }
+            _ => false,
+        };
+        if !changed {
+            continue;
+        }
+
+        let before = (list.stored(), list.config.clone());
+        if let Err(err) = list.reload() {
+            println!("{}: {}", "error".red(), err);
+            continue;
+        }
+        if list.stored() != before.0 || list.config != before.1 {
+            render(list, options);
+        }
+    }
+}
+
+/// Shows the list, then shows it again every time its files change, e.g. because of a `dodo`
+/// run in another terminal. Files are watched with filesystem notifications, or checked every
+/// second when they aren't available or `poll` is given
+pub fn run(list: &mut TodoList, options: &DisplayOptions, poll: bool) -> Result<(), Error> {
+    let folder = match list.config_path.clone() {
+        Some(folder) => folder,
+        None => {
+            return Err(Error::WatchError(
+                "the list isn't stored in a folder".into(),
+            ))
+        }
+    };
+
+    render(list, options);
+
+    if !poll {
+        match start::<RecommendedWatcher>(&folder, DEBOUNCE_DELAY) {
+            Ok((_watcher, events)) => {
+                follow(list, options, events);
+                return Ok(());
+            }
+            Err(err) => println!(
+                "{}: filesystem notifications aren't available ({}). Checking for changes every second instead.",
+                "info".yellow(),
+                err
+            ),
+        }
+    }
+
+    let (_watcher, events) = start::<PollWatcher>(&folder, POLL_INTERVAL)
+        .map_err(|err| Error::WatchError(err.to_string()))?;
+    follow(list, options, events);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_files() {
+        assert!(is_list_file(Path::new("/home/me/.config/dodo/dodo.ron")));
+        assert!(is_list_file(Path::new("queries.ron")));
+        assert!(is_list_file(Path::new("dodo.db")));
+        assert!(!is_list_file(Path::new("dodo.lock")));
+        assert!(!is_list_file(Path::new("ron_history")));
+    }
+}